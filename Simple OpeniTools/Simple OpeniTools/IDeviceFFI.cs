@@ -9,7 +9,7 @@ public struct RustResult
 }
 
 
-public enum EventKind { Connected = 0, Disconnected = 1 }
+public enum EventKind { Connected = 0, Disconnected = 1, WaitingForTrust = 2, NetworkConnected = 3 }
 
 [UnmanagedFunctionPointer(CallingConvention.Cdecl)]
 public delegate void EventCallback(EventKind ev);
@@ -144,7 +144,7 @@ namespace Simple_OpeniTools
                 this.FillDeviceInfo();
 
             }
-            else
+            else if (e == EventKind.Disconnected)
             {
                 this._deviceProvider?.Dispose();
                 this._deviceProvider = null;
@@ -0,0 +1,461 @@
+use std::path::Path;
+
+use idevice::afc::opcode::AfcFopenMode;
+use plist::{Dictionary, Value};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::idevice_helper::{get_afc_client, get_installation_proxy_client};
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::{CancelToken, plist_to_json, read_plist, send_plist};
+
+/// the attributes that are always asked for, they fill the typed fields of `AppInfo`
+const DEFAULT_ATTRIBUTES: &[&str] = &[
+    "CFBundleIdentifier",
+    "CFBundleDisplayName",
+    "CFBundleName",
+    "CFBundleShortVersionString",
+    "CFBundleVersion",
+    "ApplicationType",
+    "Path",
+    "Container",
+    "StaticDiskUsage",
+    "DynamicDiskUsage",
+];
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+pub enum AppType {
+    #[default]
+    Any,
+    User,
+    System,
+}
+
+impl AppType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Any => "Any",
+            Self::User => "User",
+            Self::System => "System",
+        }
+    }
+}
+
+/// which apps `list_apps` returns and what it asks installation_proxy for about them,
+/// a missing field matches every app
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct AppFilter {
+    pub app_type: AppType,
+    /// only return these apps, empty means all of them
+    pub bundle_ids: Vec<String>,
+    /// extra installation_proxy attributes to return in `AppInfo::attributes`
+    pub attributes: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct AppInfo {
+    pub bundle_id: String,
+    pub display_name: Option<String>,
+    pub version: Option<String>,
+    pub build: Option<String>,
+    pub app_type: Option<String>,
+    pub path: Option<String>,
+    pub container: Option<String>,
+    pub static_size: Option<u64>,
+    pub dynamic_size: Option<u64>,
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+impl AppInfo {
+    fn from_dict(dict: &Dictionary, extra: &[String]) -> Self {
+        let string = |key: &str| {
+            dict.get(key)
+                .and_then(Value::as_string)
+                .map(ToString::to_string)
+        };
+        let unsigned = |key: &str| dict.get(key).and_then(Value::as_unsigned_integer);
+
+        Self {
+            bundle_id: string("CFBundleIdentifier").unwrap_or_default(),
+            display_name: string("CFBundleDisplayName").or_else(|| string("CFBundleName")),
+            version: string("CFBundleShortVersionString"),
+            build: string("CFBundleVersion"),
+            app_type: string("ApplicationType"),
+            path: string("Path"),
+            container: string("Container"),
+            static_size: unsigned("StaticDiskUsage"),
+            dynamic_size: unsigned("DynamicDiskUsage"),
+            attributes: extra
+                .iter()
+                .filter_map(|k| dict.get(k).map(|v| (k.clone(), plist_to_json(v))))
+                .collect(),
+        }
+    }
+}
+
+pub async fn list_apps(
+    provider: &DeviceProvider,
+    filter: AppFilter,
+) -> Result<Vec<AppInfo>, String> {
+    let mut instproxy = get_installation_proxy_client(provider).await?;
+
+    let return_attributes = DEFAULT_ATTRIBUTES
+        .iter()
+        .map(|s| s.to_string())
+        .chain(filter.attributes.iter().cloned())
+        .map(Value::String)
+        .collect::<Vec<_>>();
+
+    let mut options = Dictionary::new();
+    options.insert(
+        "ApplicationType".into(),
+        Value::String(filter.app_type.as_str().into()),
+    );
+    options.insert("ReturnAttributes".into(), Value::Array(return_attributes));
+    if !filter.bundle_ids.is_empty() {
+        options.insert(
+            "BundleIDs".into(),
+            Value::Array(
+                filter
+                    .bundle_ids
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect(),
+            ),
+        );
+    }
+
+    let apps = instproxy
+        .browse(Some(Value::Dictionary(options)))
+        .await
+        .map_err(|e| format!("failed to browse the installed apps: {e:?}"))?;
+
+    let mut apps = apps
+        .iter()
+        .filter_map(Value::as_dictionary)
+        .map(|dict| AppInfo::from_dict(dict, &filter.attributes))
+        .collect::<Vec<_>>();
+
+    apps.sort_by(|a, b| a.bundle_id.cmp(&b.bundle_id));
+
+    Ok(apps)
+}
+
+/// which half of a streamed install a progress report belongs to
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum InstallPhase {
+    Upload = 0,
+    Install,
+}
+
+/// where installation_proxy expects packages to be uploaded before installing them
+const STAGING_DIR: &str = "PublicStaging";
+
+/// an installation_proxy command that reports its progress until it's complete
+#[derive(Debug, Clone, Copy)]
+pub enum AppCommand {
+    Install,
+    Upgrade,
+    Uninstall,
+    Archive,
+    Restore,
+}
+
+impl AppCommand {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Install => "Install",
+            Self::Upgrade => "Upgrade",
+            Self::Uninstall => "Uninstall",
+            Self::Archive => "Archive",
+            Self::Restore => "Restore",
+        }
+    }
+}
+
+/// sends `command` and reports every `PercentComplete` and `Status` the device sends back
+pub async fn run_app_command(
+    provider: &DeviceProvider,
+    command: AppCommand,
+    fields: Dictionary,
+    mut on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    let mut instproxy = get_installation_proxy_client(provider).await?;
+
+    let mut req = Dictionary::new();
+    req.insert("Command".into(), Value::String(command.as_str().into()));
+    req.extend(fields);
+
+    send_plist(&mut instproxy.idevice, Value::Dictionary(req))
+        .await
+        .map_err(|e| format!("failed to send the {} command: {e:?}", command.as_str()))?;
+
+    let mut percent = 0;
+    loop {
+        let res = read_plist(&mut instproxy.idevice)
+            .await
+            .map_err(|e| format!("lost the {} command response: {e:?}", command.as_str()))?;
+
+        if let Some(err) = res.get("Error").and_then(Value::as_string) {
+            let description = res
+                .get("ErrorDescription")
+                .and_then(Value::as_string)
+                .unwrap_or_default();
+
+            return Err(format!(
+                "{} failed with {err}: {description}",
+                command.as_str()
+            ));
+        }
+
+        if let Some(p) = res
+            .get("PercentComplete")
+            .and_then(Value::as_unsigned_integer)
+        {
+            percent = p.min(100) as u8;
+        }
+
+        match res.get("Status").and_then(Value::as_string) {
+            Some("Complete") => {
+                on_progress(100, "Complete");
+                return Ok(());
+            }
+            Some(status) => on_progress(percent, status),
+            None => {}
+        }
+    }
+}
+
+fn bundle_id_fields(bundle_id: &str) -> Dictionary {
+    let mut fields = Dictionary::new();
+    fields.insert(
+        "ApplicationIdentifier".into(),
+        Value::String(bundle_id.into()),
+    );
+    fields
+}
+
+/// copies `src` into the device file in chunks, checking `cancel` between every chunk
+async fn upload_chunks(
+    src: &mut (impl AsyncRead + Unpin),
+    dst: &mut (impl AsyncWrite + Unpin),
+    total: u64,
+    cancel: &CancelToken,
+    mut on_upload: impl FnMut(u8),
+) -> Result<(), String> {
+    let mut buf = vec![0u8; 512 * 1024];
+    let mut amount_sent: u64 = 0;
+    let mut last_progress = None;
+
+    loop {
+        if cancel.is_cancelled() {
+            return Err("the upload was cancelled".into());
+        }
+
+        let n = src
+            .read(&mut buf)
+            .await
+            .map_err(|e| format!("failed to read the package: {e:?}"))?;
+        if n == 0 {
+            break;
+        }
+
+        dst.write_all(&buf[..n])
+            .await
+            .map_err(|e| format!("failed to upload the package: {e:?}"))?;
+        amount_sent += n as u64;
+
+        let progress = match total {
+            0 => 100,
+            total => ((amount_sent * 100) / total).min(100) as u8,
+        };
+        if last_progress != Some(progress) {
+            on_upload(progress);
+            last_progress = Some(progress);
+        }
+    }
+
+    Ok(())
+}
+
+/// uploads a package into the staging directory under a name of its own, so concurrent
+/// installs don't overwrite each other, and returns its path on the device. a cancelled or
+/// failed upload removes the partial file
+async fn stage_package(
+    provider: &DeviceProvider,
+    src: &mut (impl AsyncRead + Unpin),
+    total: u64,
+    cancel: &CancelToken,
+    on_upload: impl FnMut(u8),
+) -> Result<String, String> {
+    let mut afc = get_afc_client(provider).await?;
+
+    // it's fine if it already exists
+    let _ = afc.mk_dir(STAGING_DIR).await;
+
+    let staged_path = format!("{STAGING_DIR}/{}.ipa", uuid::Uuid::new_v4());
+    let mut dst = afc
+        .open(&staged_path, AfcFopenMode::WrOnly)
+        .await
+        .map_err(|e| format!("failed to create {staged_path}: {e:?}"))?;
+
+    let uploaded = upload_chunks(src, &mut dst, total, cancel, on_upload).await;
+    let closed = dst
+        .close()
+        .await
+        .map_err(|e| format!("failed to close {staged_path}: {e:?}"));
+
+    if let Err(e) = uploaded.and(closed) {
+        let _ = afc.remove(&staged_path).await;
+        return Err(e);
+    }
+
+    Ok(staged_path)
+}
+
+/// streams a package from disk into the staging directory without loading it into memory
+async fn stage_file(
+    provider: &DeviceProvider,
+    path: &Path,
+    cancel: &CancelToken,
+    on_upload: impl FnMut(u8),
+) -> Result<String, String> {
+    let mut src = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("failed to open {}: {e:?}", path.display()))?;
+
+    let total = src
+        .metadata()
+        .await
+        .map_err(|e| format!("failed to read the size of {}: {e:?}", path.display()))?
+        .len();
+
+    stage_package(provider, &mut src, total, cancel, on_upload).await
+}
+
+fn package_fields(staged_path: String) -> Dictionary {
+    let mut fields = Dictionary::new();
+    fields.insert("PackagePath".into(), Value::String(staged_path));
+    fields.insert("ClientOptions".into(), Value::Dictionary(Dictionary::new()));
+    fields
+}
+
+/// installs a staged package, installation_proxy consumes it on success and a failed
+/// install removes it
+async fn install_staged(
+    provider: &DeviceProvider,
+    command: AppCommand,
+    staged_path: String,
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    let res = run_app_command(
+        provider,
+        command,
+        package_fields(staged_path.clone()),
+        on_progress,
+    )
+    .await;
+
+    if res.is_err()
+        && let Ok(mut afc) = get_afc_client(provider).await
+    {
+        let _ = afc.remove(&staged_path).await;
+    }
+
+    res
+}
+
+/// like `install_path` for a package that's already in memory, it can't be cancelled
+pub async fn install_bytes(
+    provider: &DeviceProvider,
+    data: impl AsRef<[u8]>,
+    command: AppCommand,
+    on_upload: impl FnMut(u8),
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    let data = data.as_ref();
+    let staged_path = stage_package(
+        provider,
+        &mut &*data,
+        data.len() as u64,
+        &CancelToken::new(),
+        on_upload,
+    )
+    .await?;
+
+    install_staged(provider, command, staged_path, on_progress).await
+}
+
+/// streams the package at `path` to the device then installs it, uploading and installing
+/// report their progress separately. cancelling only works until the upload is done, after
+/// that installation_proxy owns the package
+pub async fn install_path(
+    provider: &DeviceProvider,
+    path: impl AsRef<Path>,
+    command: AppCommand,
+    cancel: &CancelToken,
+    on_upload: impl FnMut(u8),
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    let staged_path = stage_file(provider, path.as_ref(), cancel, on_upload).await?;
+
+    if cancel.is_cancelled() {
+        let mut afc = get_afc_client(provider).await?;
+        let _ = afc.remove(&staged_path).await;
+        return Err("the install was cancelled".into());
+    }
+
+    install_staged(provider, command, staged_path, on_progress).await
+}
+
+pub async fn uninstall(
+    provider: &DeviceProvider,
+    bundle_id: &str,
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    run_app_command(
+        provider,
+        AppCommand::Uninstall,
+        bundle_id_fields(bundle_id),
+        on_progress,
+    )
+    .await
+}
+
+/// archives the app, `skip_uninstall` keeps the app installed next to its archive
+pub async fn archive(
+    provider: &DeviceProvider,
+    bundle_id: &str,
+    skip_uninstall: bool,
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    let mut options = Dictionary::new();
+    options.insert("SkipUninstall".into(), Value::Boolean(skip_uninstall));
+    options.insert(
+        "ArchiveType".into(),
+        Value::String("ApplicationOnly".into()),
+    );
+
+    let mut fields = bundle_id_fields(bundle_id);
+    fields.insert("ClientOptions".into(), Value::Dictionary(options));
+
+    run_app_command(provider, AppCommand::Archive, fields, on_progress).await
+}
+
+pub async fn restore_archive(
+    provider: &DeviceProvider,
+    bundle_id: &str,
+    on_progress: impl FnMut(u8, &str),
+) -> Result<(), String> {
+    run_app_command(
+        provider,
+        AppCommand::Restore,
+        bundle_id_fields(bundle_id),
+        on_progress,
+    )
+    .await
+}
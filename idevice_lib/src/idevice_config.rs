@@ -0,0 +1,103 @@
+use std::{
+    future::Future,
+    io,
+    sync::{Arc, LazyLock, RwLock},
+    time::Duration,
+};
+
+use idevice::{IdeviceError, usbmuxd::UsbmuxdAddr};
+use serde::Deserialize;
+use tokio::sync::Semaphore;
+
+use crate::idevice_provider::parse_usbmuxd_addr;
+
+pub const DEFAULT_LABEL: &str = "openitools-idevice";
+
+/// what the host passes to `configure`, a missing field means the default
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// `host:port` or a unix socket path. without it `USBMUXD_SOCKET_ADDRESS` is used, and
+    /// without that the platform's usbmuxd
+    pub usbmuxd_address: Option<String>,
+    /// the name the device sees connections under, `DEFAULT_LABEL` without it
+    pub label: Option<String>,
+    /// how long connecting to usbmuxd or to a service on the device may take, no limit without it
+    pub connect_timeout_secs: Option<u64>,
+    /// how many calls may use devices at the same time, no limit without it
+    pub max_concurrent_operations: Option<usize>,
+}
+
+#[derive(Default)]
+struct State {
+    config: Config,
+    operations: Option<Arc<Semaphore>>,
+}
+
+static STATE: LazyLock<RwLock<State>> = LazyLock::new(Default::default);
+
+/// replaces the whole config. providers made before keep their usbmuxd address and label, calls
+/// that already hold an operation slot finish under the old limit
+pub fn configure(config: Config) -> Result<(), String> {
+    if let Some(addr) = &config.usbmuxd_address {
+        parse_usbmuxd_addr(addr)?;
+    }
+    if config.connect_timeout_secs == Some(0) {
+        return Err("connect_timeout_secs has to be at least 1".into());
+    }
+    if config.max_concurrent_operations == Some(0) {
+        return Err("max_concurrent_operations has to be at least 1".into());
+    }
+
+    let operations = config
+        .max_concurrent_operations
+        .map(|n| Arc::new(Semaphore::new(n)));
+
+    *STATE.write().unwrap() = State { config, operations };
+    Ok(())
+}
+
+pub fn usbmuxd_addr() -> Result<UsbmuxdAddr, String> {
+    match &STATE.read().unwrap().config.usbmuxd_address {
+        Some(addr) => parse_usbmuxd_addr(addr),
+        None => UsbmuxdAddr::from_env_var()
+            .map_err(|e| format!("failed to create a usbmuxd address from env: {e:?}")),
+    }
+}
+
+pub fn label() -> String {
+    STATE
+        .read()
+        .unwrap()
+        .config
+        .label
+        .clone()
+        .unwrap_or_else(|| DEFAULT_LABEL.into())
+}
+
+/// `None` when there's no limit
+pub fn operation_limit() -> Option<Arc<Semaphore>> {
+    STATE.read().unwrap().operations.clone()
+}
+
+/// fails `connect` with a `TimedOut` socket error once the configured connect timeout runs out
+pub async fn with_connect_timeout<T>(
+    connect: impl Future<Output = Result<T, IdeviceError>>,
+) -> Result<T, IdeviceError> {
+    let connect_timeout = STATE
+        .read()
+        .unwrap()
+        .config
+        .connect_timeout_secs
+        .map(Duration::from_secs);
+
+    match connect_timeout {
+        Some(t) => tokio::time::timeout(t, connect).await.map_err(|_| {
+            IdeviceError::Socket(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("connecting took longer than {}s", t.as_secs()),
+            ))
+        })?,
+        None => connect.await,
+    }
+}
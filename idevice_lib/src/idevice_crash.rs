@@ -0,0 +1,292 @@
+use std::path::Path;
+
+use idevice::{IdeviceService, crashreportcopymobile::CrashReportCopyMobileClient};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::idevice_provider::DeviceProvider;
+
+async fn get_crash_client(
+    provider: &DeviceProvider,
+) -> Result<CrashReportCopyMobileClient, String> {
+    // moves the new reports into the directory the copy service serves
+    idevice::crashreportcopymobile::flush_reports(provider)
+        .await
+        .map_err(|e| format!("failed to flush the crash reports: {e:?}"))?;
+
+    CrashReportCopyMobileClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to the crash report service: {e:?}"))
+}
+
+/// the reports in `dir`, or at the top when it's `None`. the paths are relative to the top
+/// of the crash report directory, like `download_crash_report` and `delete_crash_report` take
+/// them, so `Retired` lists `Retired/foo.ips`
+pub async fn list_crash_reports(
+    provider: &DeviceProvider,
+    dir: Option<&str>,
+) -> Result<Vec<String>, String> {
+    let mut crash_client = get_crash_client(provider).await?;
+
+    let prefix = dir
+        .map(|d| d.trim_matches('/'))
+        .filter(|d| !d.is_empty())
+        .map(|d| format!("{d}/"))
+        .unwrap_or_default();
+
+    let mut reports = crash_client
+        .ls(dir)
+        .await
+        .map_err(|e| format!("failed to list the crash reports: {e:?}"))?
+        .into_iter()
+        .filter(|s| s.as_str() != "." && s.as_str() != "..")
+        .map(|name| format!("{prefix}{name}"))
+        .collect::<Vec<_>>();
+    reports.sort();
+
+    Ok(reports)
+}
+
+/// saves the report at `name`, a path from `list_crash_reports`, into `dest`, `delete` removes it from the device afterwards
+pub async fn download_crash_report(
+    provider: &DeviceProvider,
+    name: &str,
+    dest: impl AsRef<Path>,
+    delete: bool,
+) -> Result<(), String> {
+    let dest = dest.as_ref();
+    let mut crash_client = get_crash_client(provider).await?;
+
+    let data = crash_client
+        .pull(name)
+        .await
+        .map_err(|e| format!("failed to download {name}: {e:?}"))?;
+
+    tokio::fs::write(dest, data)
+        .await
+        .map_err(|e| format!("failed to write {}: {e:?}", dest.display()))?;
+
+    if delete {
+        crash_client
+            .remove(name)
+            .await
+            .map_err(|e| format!("failed to delete {name}: {e:?}"))?;
+    }
+
+    Ok(())
+}
+
+pub async fn delete_crash_report(provider: &DeviceProvider, name: &str) -> Result<(), String> {
+    let mut crash_client = get_crash_client(provider).await?;
+
+    crash_client
+        .remove(name)
+        .await
+        .map_err(|e| format!("failed to delete {name}: {e:?}"))
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CrashFrame {
+    pub image: Option<String>,
+    pub symbol: Option<String>,
+    pub image_offset: Option<u64>,
+    pub symbol_offset: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct CrashReport {
+    pub app_name: Option<String>,
+    pub bundle_id: Option<String>,
+    pub app_version: Option<String>,
+    pub os_version: Option<String>,
+    pub bug_type: Option<String>,
+    pub timestamp: Option<String>,
+    pub exception_type: Option<String>,
+    pub exception_signal: Option<String>,
+    pub termination_reason: Option<String>,
+    pub crashed_thread: Option<u64>,
+    pub frames: Vec<CrashFrame>,
+    /// the report body when it isn't json, like the text reports older iOS versions write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_body: Option<String>,
+}
+
+fn json_string(value: &Value, pointer: &str) -> Option<String> {
+    value.pointer(pointer).and_then(|v| match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+/// reads a `.ips` report, a one line json header followed by a json body
+pub fn parse_ips(data: &[u8]) -> Result<CrashReport, String> {
+    let text = String::from_utf8_lossy(data);
+    let (header, body) = text.split_once('\n').unwrap_or((&text, ""));
+
+    let header: Value =
+        serde_json::from_str(header).map_err(|e| format!("the report header is not json: {e}"))?;
+
+    let mut report = CrashReport {
+        app_name: json_string(&header, "/app_name").or_else(|| json_string(&header, "/name")),
+        bundle_id: json_string(&header, "/bundleID"),
+        app_version: json_string(&header, "/app_version"),
+        os_version: json_string(&header, "/os_version"),
+        bug_type: json_string(&header, "/bug_type"),
+        timestamp: json_string(&header, "/timestamp"),
+        ..Default::default()
+    };
+
+    let body: Value = match serde_json::from_str(body) {
+        Ok(body) => body,
+        Err(_) => {
+            report.raw_body = Some(body.to_string()).filter(|b| !b.trim().is_empty());
+            return Ok(report);
+        }
+    };
+
+    report.app_name = report.app_name.or_else(|| json_string(&body, "/procName"));
+    report.bundle_id = report
+        .bundle_id
+        .or_else(|| json_string(&body, "/bundleInfo/CFBundleIdentifier"));
+    report.timestamp = json_string(&body, "/captureTime").or(report.timestamp);
+    report.exception_type = json_string(&body, "/exception/type");
+    report.exception_signal = json_string(&body, "/exception/signal");
+    report.termination_reason = json_string(&body, "/termination/indicator")
+        .or_else(|| json_string(&body, "/termination/reasons/0"));
+
+    let images = body
+        .get("usedImages")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    let threads = body
+        .get("threads")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+
+    // `faultingThread` is an index into `threads`, fall back to the one marked as triggered
+    let crashed_thread = body
+        .get("faultingThread")
+        .and_then(Value::as_u64)
+        .or_else(|| {
+            threads
+                .iter()
+                .position(|t| t.get("triggered").and_then(Value::as_bool) == Some(true))
+                .map(|i| i as u64)
+        });
+    report.crashed_thread = crashed_thread;
+
+    if let Some(thread) = crashed_thread.and_then(|i| threads.get(i as usize)) {
+        report.frames = thread
+            .get("frames")
+            .and_then(Value::as_array)
+            .map(|frames| {
+                frames
+                    .iter()
+                    .map(|frame| CrashFrame {
+                        image: frame
+                            .get("imageIndex")
+                            .and_then(Value::as_u64)
+                            .and_then(|i| images.get(i as usize))
+                            .and_then(|image| json_string(image, "/name")),
+                        symbol: json_string(frame, "/symbol"),
+                        image_offset: frame.get("imageOffset").and_then(Value::as_u64),
+                        symbol_offset: frame.get("symbolLocation").and_then(Value::as_u64),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = r#"{"app_name":"Maps","bundleID":"com.apple.Maps","app_version":"1.0","os_version":"iPhone OS 17.4 (21E219)","bug_type":"309","timestamp":"2024-04-02 10:11:12.00 +0200"}"#;
+
+    #[test]
+    fn parse_ips_reads_the_crashed_thread() {
+        let body = r#"{
+            "captureTime": "2024-04-02 10:11:12.3456 +0200",
+            "exception": {"type": "EXC_BAD_ACCESS", "signal": "SIGSEGV"},
+            "termination": {"indicator": "Namespace SIGNAL, Code 11"},
+            "usedImages": [{"name": "Maps"}, {"name": "libsystem_kernel.dylib"}],
+            "threads": [
+                {"frames": []},
+                {"triggered": true, "frames": [
+                    {"imageIndex": 1, "imageOffset": 4096, "symbol": "__pthread_kill", "symbolLocation": 8},
+                    {"imageIndex": 0, "imageOffset": 512}
+                ]}
+            ]
+        }"#;
+
+        let report = parse_ips(format!("{HEADER}\n{body}").as_bytes()).unwrap();
+
+        assert_eq!(report.app_name.as_deref(), Some("Maps"));
+        assert_eq!(report.bundle_id.as_deref(), Some("com.apple.Maps"));
+        assert_eq!(report.bug_type.as_deref(), Some("309"));
+        assert_eq!(
+            report.timestamp.as_deref(),
+            Some("2024-04-02 10:11:12.3456 +0200")
+        );
+        assert_eq!(report.exception_type.as_deref(), Some("EXC_BAD_ACCESS"));
+        assert_eq!(report.exception_signal.as_deref(), Some("SIGSEGV"));
+        assert_eq!(report.crashed_thread, Some(1));
+        assert_eq!(report.frames.len(), 2);
+        assert_eq!(
+            report.frames[0].image.as_deref(),
+            Some("libsystem_kernel.dylib")
+        );
+        assert_eq!(report.frames[0].symbol.as_deref(), Some("__pthread_kill"));
+        assert_eq!(report.frames[0].symbol_offset, Some(8));
+        assert_eq!(report.frames[1].symbol, None);
+        assert!(report.raw_body.is_none());
+    }
+
+    #[test]
+    fn parse_ips_prefers_faulting_thread_and_tolerates_bad_indices() {
+        let body = r#"{"faultingThread": 0, "threads": [{"frames": [{"imageIndex": 9}]}]}"#;
+
+        let report = parse_ips(format!("{HEADER}\n{body}").as_bytes()).unwrap();
+
+        assert_eq!(report.crashed_thread, Some(0));
+        assert_eq!(report.frames.len(), 1);
+        assert_eq!(report.frames[0].image, None);
+    }
+
+    #[test]
+    fn parse_ips_keeps_a_text_body_raw() {
+        let report = parse_ips(format!("{HEADER}\nIncident Identifier: 1234").as_bytes()).unwrap();
+
+        assert_eq!(report.app_name.as_deref(), Some("Maps"));
+        assert_eq!(
+            report.raw_body.as_deref(),
+            Some("Incident Identifier: 1234")
+        );
+        assert!(report.frames.is_empty());
+    }
+
+    #[test]
+    fn parse_ips_handles_a_header_only_report() {
+        let report = parse_ips(HEADER.as_bytes()).unwrap();
+
+        assert_eq!(
+            report.os_version.as_deref(),
+            Some("iPhone OS 17.4 (21E219)")
+        );
+        assert!(report.raw_body.is_none());
+    }
+
+    #[test]
+    fn parse_ips_rejects_a_header_that_is_not_json() {
+        assert!(parse_ips(b"").is_err());
+        assert!(parse_ips(b"Incident Identifier: 1234\n{}").is_err());
+        assert!(parse_ips(&[0xff, 0xfe, b'\n', b'{', b'}']).is_err());
+    }
+}
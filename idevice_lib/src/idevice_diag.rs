@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use plist::Value;
+use tokio::time::{sleep, timeout};
+
+use crate::idevice_helper::get_diag_client;
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::plist_to_lossless_json;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerAction {
+    Restart = 0,
+    Shutdown,
+    Sleep,
+}
+
+impl TryFrom<u32> for PowerAction {
+    type Error = String;
+
+    fn try_from(action: u32) -> Result<Self, Self::Error> {
+        match action {
+            0 => Ok(Self::Restart),
+            1 => Ok(Self::Shutdown),
+            2 => Ok(Self::Sleep),
+            _ => Err(format!("{action} is not a power action")),
+        }
+    }
+}
+
+/// polls usbmuxd until the provider's own device is `connected` or gone
+async fn wait_for_device(provider: &DeviceProvider, connected: bool) {
+    while provider.find_again().await.is_ok() != connected {
+        sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// sends `action` through the diagnostics relay. with `wait` a restart or shutdown also
+/// waits for the device to disconnect, and a restart for it to come back, failing once
+/// `wait_timeout` runs out. only devices found through usbmuxd can be waited for
+pub async fn power_action(
+    provider: &DeviceProvider,
+    action: PowerAction,
+    wait: bool,
+    wait_timeout: Duration,
+) -> Result<(), String> {
+    // a sleeping device stays connected, there is nothing to wait for
+    let wait = wait && action != PowerAction::Sleep;
+
+    if wait && wait_timeout.is_zero() {
+        return Err("waiting for the device needs a timeout".into());
+    }
+    if wait && provider.usbmuxd().is_none() {
+        return Err("only devices found through usbmuxd can be waited for".into());
+    }
+
+    let mut device_diag = get_diag_client(provider).await?;
+
+    match action {
+        PowerAction::Restart => device_diag.restart().await,
+        PowerAction::Shutdown => device_diag.shutdown().await,
+        PowerAction::Sleep => device_diag.sleep().await,
+    }
+    .map_err(|e| format!("the device refused to {action:?}: {e:?}"))?;
+
+    if !wait {
+        return Ok(());
+    }
+
+    timeout(wait_timeout, async {
+        wait_for_device(provider, false).await;
+
+        if action == PowerAction::Restart {
+            wait_for_device(provider, true).await;
+        }
+    })
+    .await
+    .map_err(|_| format!("timed out waiting for the device after {action:?}"))
+}
+
+/// looks entries up by any mix of plane, name and class, e.g. the class `AppleSmartBattery`
+pub async fn query_ioregistry(
+    provider: &DeviceProvider,
+    plane: Option<&str>,
+    name: Option<&str>,
+    class: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    let mut device_diag = get_diag_client(provider).await?;
+
+    let entry = device_diag
+        .ioregistry(plane, name, class)
+        .await
+        .map_err(|e| format!("failed to query the ioregistry: {e:?}"))?
+        .ok_or("the ioregistry has no matching entry")?;
+
+    Ok(plist_to_lossless_json(&Value::Dictionary(entry)))
+}
+
+/// newer iOS versions answer most keys with a `MobileGestaltDeprecated` status instead
+pub async fn query_mobilegestalt(
+    provider: &DeviceProvider,
+    keys: Vec<String>,
+) -> Result<serde_json::Value, String> {
+    if keys.is_empty() {
+        return Err("mobilegestalt needs at least one key".into());
+    }
+
+    let mut device_diag = get_diag_client(provider).await?;
+
+    let values = device_diag
+        .mobilegestalt(Some(keys))
+        .await
+        .map_err(|e| format!("failed to query mobilegestalt: {e:?}"))?
+        .unwrap_or_default();
+
+    Ok(plist_to_lossless_json(&Value::Dictionary(values)))
+}
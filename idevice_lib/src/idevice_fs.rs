@@ -3,11 +3,12 @@ use std::{ffi::CString, path::Path, ptr};
 use idevice::{
     IdeviceService,
     afc::{AfcClient, FileInfo},
-    provider::UsbmuxdProvider,
 };
 
 use std::os::raw::c_char;
 
+use crate::idevice_provider::DeviceProvider;
+
 #[repr(C)]
 pub struct FSTreeFFI {
     pub path: *mut c_char,
@@ -187,7 +188,7 @@ impl std::fmt::Display for FileType {
     }
 }
 
-pub async fn list_path(path: String, provider: &UsbmuxdProvider) -> FSTree {
+pub async fn list_path(path: String, provider: &DeviceProvider) -> FSTree {
     let mut afc = AfcClient::connect(provider).await.unwrap();
 
     let afc_path_info: PathInfo = afc.get_file_info(&path).await.unwrap().into();
@@ -1,38 +1,46 @@
+use crate::idevice_config::{label, usbmuxd_addr, with_connect_timeout};
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_region::{ModelNumberInfo, decode_model_number};
 use crate::idevice_utils::{RecursiveFind, get_string_value_or_default};
+use plist::Value;
+use serde::Serialize;
 use std::{
     ffi::{CString, c_char},
-    net::SocketAddr,
-    str::FromStr as _,
+    time::Duration,
 };
 
+use idevice::{
+    IdeviceError, afc::AfcClient, diagnostics_relay::DiagnosticsRelayClient,
+    installation_proxy::InstallationProxyClient, springboardservices::SpringBoardServicesClient,
+    syslog_relay::SyslogRelayClient,
+};
 pub use idevice::{
     IdeviceService,
     lockdown::LockdownClient,
-    provider::{IdeviceProvider, UsbmuxdProvider},
-    usbmuxd::{UsbmuxdAddr, UsbmuxdConnection, UsbmuxdDevice},
+    provider::IdeviceProvider,
+    usbmuxd::{Connection, UsbmuxdConnection, UsbmuxdDevice},
 };
-use idevice::{diagnostics_relay::DiagnosticsRelayClient, syslog_relay::SyslogRelayClient};
+use tokio::time::{sleep, timeout};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Event {
     Connected,
     Disconnected,
+    /// the Trust dialog is up on the device, or the device has to be unlocked to show it
+    WaitingForTrust,
+    /// the device showed up on usbmuxd over the network after Wi-Fi connections were enabled
+    NetworkConnected,
+}
+
+pub async fn connect_usbmuxd() -> Result<UsbmuxdConnection, String> {
+    with_connect_timeout(usbmuxd_addr()?.connect(1))
+        .await
+        .map_err(|e| format!("Unable to connect to usbmxud: {e:?}"))
 }
 
 async fn get_devices() -> Result<Vec<UsbmuxdDevice>, String> {
-    let mut usbmuxd = if let Ok(var) = std::env::var("USBMUXD_SOCKET_ADDRESS") {
-        let socket =
-            SocketAddr::from_str(&var).map_err(|e| format!("Bad USBMUXD_SOCKET_ADDRESS: {e:?}"))?;
-        let socket = tokio::net::TcpStream::connect(socket)
-            .await
-            .map_err(|e| format!("unable to connect to socket address: {e:?}"))?;
-        UsbmuxdConnection::new(Box::new(socket), 1)
-    } else {
-        UsbmuxdConnection::default()
-            .await
-            .map_err(|e| format!("Unable to connect to usbmxud: {e:?}"))?
-    };
+    let mut usbmuxd = connect_usbmuxd().await?;
     let devices = usbmuxd
         .get_devices()
         .await
@@ -41,108 +49,284 @@ async fn get_devices() -> Result<Vec<UsbmuxdDevice>, String> {
     Ok(devices)
 }
 
-async fn get_device() -> Result<UsbmuxdDevice, String> {
-    let mut devices = get_devices().await?;
+/// which connection a device may be picked through
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionKind {
+    /// usb when the device is plugged in, otherwise the network
+    Any,
+    Usb,
+    Network,
+}
+
+impl TryFrom<u32> for ConnectionKind {
+    type Error = String;
+
+    fn try_from(kind: u32) -> Result<Self, Self::Error> {
+        match kind {
+            0 => Ok(Self::Any),
+            1 => Ok(Self::Usb),
+            2 => Ok(Self::Network),
+            _ => Err(format!("{kind} is not a connection kind")),
+        }
+    }
+}
 
-    if devices.is_empty() {
-        return Err("devices are empty".into());
+impl ConnectionKind {
+    fn allows(self, connection: &Connection) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Usb => *connection == Connection::Usb,
+            Self::Network => matches!(connection, Connection::Network(_)),
+        }
     }
+}
 
-    Ok(devices.remove(0))
+async fn get_device(kind: ConnectionKind) -> Result<UsbmuxdDevice, String> {
+    let devices = get_devices().await?;
+
+    // usbmuxd lists a device once per connection, usb is faster so it goes first
+    devices
+        .into_iter()
+        .filter(|d| kind.allows(&d.connection_type))
+        .min_by_key(|d| d.connection_type != Connection::Usb)
+        .ok_or(match kind {
+            ConnectionKind::Any => "devices are empty".into(),
+            ConnectionKind::Usb => "no devices are connected over usb".into(),
+            ConnectionKind::Network => "no devices are reachable over the network".into(),
+        })
 }
 
-pub async fn get_provider() -> Result<UsbmuxdProvider, String> {
-    let device = get_device().await?;
+/// polls usbmuxd until `udid` shows up over the network, `false` once `wait_timeout` runs out
+pub async fn wait_for_network_device(udid: &str, wait_timeout: Duration) -> bool {
+    timeout(wait_timeout, async {
+        loop {
+            if let Ok(devices) = get_devices().await
+                && devices
+                    .iter()
+                    .any(|d| d.udid == udid && matches!(d.connection_type, Connection::Network(_)))
+            {
+                return;
+            }
+
+            sleep(Duration::from_secs(1)).await;
+        }
+    })
+    .await
+    .is_ok()
+}
 
-    let muxaddr = UsbmuxdAddr::from_env_var()
-        .map_err(|e| format!("failed to create a usbmuxd address from env: {e:?}"))?;
+pub async fn get_provider(kind: ConnectionKind) -> Result<DeviceProvider, String> {
+    let device = get_device(kind).await?;
 
-    Ok(device.to_provider(muxaddr, "openitools-idevice"))
+    Ok(DeviceProvider::Usbmuxd(
+        device.to_provider(usbmuxd_addr()?, label()),
+    ))
 }
 
-pub async fn get_lockdownd_client(provider: &UsbmuxdProvider) -> Result<LockdownClient, String> {
+pub async fn get_lockdownd_client(provider: &DeviceProvider) -> Result<LockdownClient, String> {
     let mut lockdownd_client = LockdownClient::connect(provider)
         .await
         .map_err(|e| format!("failed to connect to lockdownd service: {e:?}"))?;
 
+    let pairing_file = provider.get_pairing_file().await.map_err(|e| match e {
+        // usbmuxd answers without a record for devices this computer never paired with
+        IdeviceError::UnexpectedResponse => {
+            "the device is not paired with this computer, call pair_device first".to_string()
+        }
+        e => format!("Failed to get the pairing file: {e:?}"),
+    })?;
+
     lockdownd_client
-        .start_session(
-            &provider
-                .get_pairing_file()
-                .await
-                .map_err(|e| format!("Failed to get the pairing file: {e:?}"))?,
-        )
+        .start_session(&pairing_file)
         .await
-        .map_err(|e| format!("Failed to start a new lockdownd session: {e:?}"))?;
+        .map_err(|e| match e {
+            IdeviceError::InvalidHostID => "the device no longer trusts this computer's pairing \
+                record, call pair_device to pair again"
+                .to_string(),
+            e => format!("Failed to start a new lockdownd session: {e:?}"),
+        })?;
 
     Ok(lockdownd_client)
 }
 
-pub async fn get_diag_client(provider: &UsbmuxdProvider) -> Result<DiagnosticsRelayClient, String> {
+pub async fn get_diag_client(provider: &DeviceProvider) -> Result<DiagnosticsRelayClient, String> {
     DiagnosticsRelayClient::connect(provider)
         .await
         .map_err(|e| format!("failed to connect to lockdownd service: {e:?}"))
 }
 
-pub async fn get_syslog_client(provider: &UsbmuxdProvider) -> Result<SyslogRelayClient, String> {
+pub async fn get_syslog_client(provider: &DeviceProvider) -> Result<SyslogRelayClient, String> {
     SyslogRelayClient::connect(provider)
         .await
         .map_err(|e| format!("failed to create a syslog relay: {e:?}"))
 }
 
-pub async fn install_package(
-    provider: &UsbmuxdProvider,
-    data: impl AsRef<[u8]>,
-) -> Result<(), String> {
-    idevice::utils::installation::install_bytes(provider, data, None)
+pub async fn get_installation_proxy_client(
+    provider: &DeviceProvider,
+) -> Result<InstallationProxyClient, String> {
+    InstallationProxyClient::connect(provider)
         .await
-        .map_err(|e| format!("failed to install package: {e:?}"))
+        .map_err(|e| format!("failed to connect to installation proxy: {e:?}"))
+}
+
+pub async fn get_afc_client(provider: &DeviceProvider) -> Result<AfcClient, String> {
+    AfcClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to afc service: {e:?}"))
+}
+
+pub async fn get_springboard_client(
+    provider: &DeviceProvider,
+) -> Result<SpringBoardServicesClient, String> {
+    SpringBoardServicesClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to springboard services: {e:?}"))
 }
 
 pub async fn is_device_connected() -> Result<(), String> {
-    get_device().await.map(|_| ())
+    get_device(ConnectionKind::Any).await.map(|_| ())
 }
 
+/// sizes in bytes
 #[repr(C)]
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, Default, Debug, Serialize)]
 pub struct Storage {
     pub total: u64,
     pub used: u64,
     pub available: u64,
 }
 
-pub async fn handle_device_storage(provider: &UsbmuxdProvider) -> Result<Storage, String> {
+/// every size `com.apple.disk_usage` reports, in bytes, `None` when the device leaves it out
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StorageBreakdown {
+    pub total_disk: Option<u64>,
+    pub total_system: Option<u64>,
+    pub system_available: Option<u64>,
+    pub total_data: Option<u64>,
+    pub data_available: Option<u64>,
+    pub data_reserved: Option<u64>,
+    /// what's free for the user, the value the Settings app shows
+    pub restore_available: Option<u64>,
+    pub apps: Option<u64>,
+    pub photos: Option<u64>,
+    pub camera: Option<u64>,
+    pub media_cache: Option<u64>,
+    pub web_app_cache: Option<u64>,
+    pub calendar: Option<u64>,
+    pub notes: Option<u64>,
+    pub voicemail: Option<u64>,
+}
+
+async fn get_disk_usage(provider: &DeviceProvider) -> Result<plist::Dictionary, String> {
     let mut lockdownd_client = get_lockdownd_client(provider).await?;
-    let total = lockdownd_client
-        .get_value(Some("TotalDiskCapacity"), Some("com.apple.disk_usage"))
-        .await
-        .map_or(0, |s| {
-            s.as_unsigned_integer().unwrap_or_default() / 1e+9 as u64
-        });
 
-    let available = lockdownd_client
-        .get_value(Some("AmountRestoreAvailable"), Some("com.apple.disk_usage"))
+    lockdownd_client
+        .get_value(None, Some("com.apple.disk_usage"))
         .await
-        .map_or(0, |s| {
-            s.as_unsigned_integer().unwrap_or_default() / 1e+9 as u64
-        });
+        .map_err(|e| format!("failed to read the disk usage: {e:?}"))?
+        .into_dictionary()
+        .ok_or_else(|| "the disk usage is not a dictionary".into())
+}
+
+pub async fn handle_device_storage(provider: &DeviceProvider) -> Result<Storage, String> {
+    let disk_usage = get_disk_usage(provider).await?;
+
+    let total = disk_usage
+        .get("TotalDiskCapacity")
+        .and_then(Value::as_unsigned_integer)
+        .unwrap_or_default();
+
+    let available = disk_usage
+        .get("AmountRestoreAvailable")
+        .and_then(Value::as_unsigned_integer)
+        .unwrap_or_default();
 
     Ok(Storage {
         total,
-        used: total - available,
+        used: total.saturating_sub(available),
         available,
     })
 }
 
+pub async fn handle_device_storage_breakdown(
+    provider: &DeviceProvider,
+) -> Result<StorageBreakdown, String> {
+    let disk_usage = get_disk_usage(provider).await?;
+    let bytes = |key: &str| disk_usage.get(key).and_then(Value::as_unsigned_integer);
+
+    Ok(StorageBreakdown {
+        total_disk: bytes("TotalDiskCapacity"),
+        total_system: bytes("TotalSystemCapacity"),
+        system_available: bytes("TotalSystemAvailable"),
+        total_data: bytes("TotalDataCapacity"),
+        data_available: bytes("TotalDataAvailable"),
+        data_reserved: bytes("AmountDataReserved"),
+        restore_available: bytes("AmountRestoreAvailable"),
+        apps: bytes("MobileApplicationUsage"),
+        photos: bytes("PhotoUsage"),
+        camera: bytes("CameraUsage"),
+        media_cache: bytes("MediaCacheUsage"),
+        web_app_cache: bytes("WebAppCacheUsage"),
+        calendar: bytes("CalendarUsage"),
+        notes: bytes("NotesUsage"),
+        voicemail: bytes("VoicemailUsage"),
+    })
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Battery {
     pub level: u64,
+    /// 0 when the device doesn't report the capacities health is computed from
     pub health: f32,
     pub cycle_counts: u64,
 }
 
-pub async fn handle_device_battery(provider: &UsbmuxdProvider) -> Result<Battery, String> {
+/// bumped whenever a field of `BatteryReport` changes meaning or goes away
+pub const BATTERY_REPORT_VERSION: u32 = 1;
+
+/// everything the battery's ioregistry entry says about it, the fields the device doesn't report
+/// are `None`
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BatteryReport {
+    pub version: u32,
+    pub source: BatterySource,
+    pub level: u64,
+    /// `full_charge_capacity` in percent of `design_capacity`
+    pub health: Option<f32>,
+    pub cycle_counts: u64,
+    /// degrees celsius
+    pub temperature: Option<f32>,
+    /// millivolts
+    pub voltage: Option<u64>,
+    /// milliamps, negative while discharging
+    pub instant_amperage: Option<i64>,
+    pub is_charging: Option<bool>,
+    pub fully_charged: Option<bool>,
+    pub external_connected: Option<bool>,
+    /// mAh the battery holds when full now
+    pub full_charge_capacity: Option<u64>,
+    /// mAh the battery held when it was new
+    pub design_capacity: Option<u64>,
+    pub serial: Option<String>,
+    pub manufacturer: Option<String>,
+    pub manufacture_date: Option<String>,
+}
+
+pub async fn handle_device_battery(provider: &DeviceProvider) -> Result<Battery, String> {
+    let report = handle_device_battery_report(provider).await?;
+
+    Ok(Battery {
+        level: report.level,
+        health: report.health.unwrap_or_default(),
+        cycle_counts: report.cycle_counts,
+    })
+}
+
+pub async fn handle_device_battery_report(
+    provider: &DeviceProvider,
+) -> Result<BatteryReport, String> {
     let mut device_diag = match get_diag_client(provider).await {
         Ok(diag) => diag,
         Err(e) => {
@@ -151,67 +335,147 @@ pub async fn handle_device_battery(provider: &UsbmuxdProvider) -> Result<Battery
         }
     };
 
-    let battery_plist_key = get_battery_plist_key(provider).await?;
-    let battery_plist = device_diag
-        .ioregistry(None, Some(battery_plist_key.as_str()), None)
-        .await
-        .unwrap_or_default()
-        .unwrap_or_default();
+    let (source, battery_plist) = get_battery_plist(provider, &mut device_diag).await?;
 
-    let level = battery_plist
-        .rfind("CurrentCapacity")
-        .map_or(0, |n| n.as_unsigned_integer().unwrap_or_default());
+    let unsigned = |key: &str| {
+        battery_plist
+            .rfind(key)
+            .and_then(|n| n.as_unsigned_integer())
+    };
+    let boolean = |key: &str| battery_plist.rfind(key).and_then(|b| b.as_boolean());
+    let string = |key: &str| {
+        battery_plist.rfind(key).and_then(|v| match v {
+            Value::String(s) => Some(s),
+            Value::Integer(i) => Some(i.to_string()),
+            _ => None,
+        })
+    };
 
-    let cycle_counts = battery_plist
-        .rfind("CycleCount")
-        .map_or(0, |n| n.as_unsigned_integer().unwrap_or_default());
+    let level = unsigned("CurrentCapacity").unwrap_or_default();
 
-    let health = {
-        let designed_capa = battery_plist
-            .rfind("DesignCapacity")
-            .map_or(0, |n| n.as_unsigned_integer().unwrap_or_default())
-            as f32;
+    let cycle_counts = unsigned("CycleCount").unwrap_or_default();
 
-        let max_capa = battery_plist
-            .rfind("NominalChargeCapacity")
-            .map_or(0, |n| n.as_unsigned_integer().unwrap_or_default())
-            as f32;
+    let design_capacity = unsigned("DesignCapacity");
+    let full_charge_capacity =
+        unsigned("NominalChargeCapacity").or_else(|| unsigned("AppleRawMaxCapacity"));
 
-        ((max_capa / designed_capa) * 100.0 * 100.0).round() / 100.0
+    let health = match (full_charge_capacity, design_capacity) {
+        (Some(max_capa), Some(designed_capa)) if designed_capa > 0 => {
+            let health = max_capa as f32 / designed_capa as f32;
+            Some((health * 100.0 * 100.0).round() / 100.0)
+        }
+        _ => None,
     };
 
-    Ok(dbg!(Battery {
+    // the amperage is signed but some devices send it as a wrapped unsigned integer
+    let instant_amperage = battery_plist.rfind("InstantAmperage").and_then(|n| {
+        n.as_signed_integer()
+            .or_else(|| n.as_unsigned_integer().map(|u| u as i64))
+    });
+
+    Ok(BatteryReport {
+        version: BATTERY_REPORT_VERSION,
+        source,
         level,
         health,
         cycle_counts,
-    }))
+        // reported in hundredths of a degree
+        temperature: unsigned("Temperature").map(|t| t as f32 / 100.0),
+        voltage: unsigned("Voltage"),
+        instant_amperage,
+        is_charging: boolean("IsCharging"),
+        fully_charged: boolean("FullyCharged"),
+        external_connected: boolean("ExternalConnected"),
+        full_charge_capacity,
+        design_capacity,
+        serial: string("Serial"),
+        manufacturer: string("Manufacturer"),
+        manufacture_date: string("ManufactureDate"),
+    })
 }
 
-async fn get_battery_plist_key(provider: &UsbmuxdProvider) -> Result<String, String> {
-    let mut lockdownd_client = match get_lockdownd_client(provider).await {
-        Ok(lockdownd) => lockdownd,
-        Err(e) => {
-            // log::error!("Something went wrong while getting the lockdownd client: {e:?}");
-            return Err(format!("was not able to get the lockdownd: {e}"));
+/// the ioregistry entry the battery values were read from
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum BatterySource {
+    #[default]
+    AppleSmartBattery,
+    AppleARMPMUCharger,
+}
+
+impl BatterySource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AppleSmartBattery => "AppleSmartBattery",
+            Self::AppleARMPMUCharger => "AppleARMPMUCharger",
         }
+    }
+}
+
+/// which entry to try first, `product_type` is like `iPhone9,3`, `iPad8,1` or `iPod9,1`.
+/// it's only a guess to save a round trip, both entries are tried either way
+fn battery_source_order(product_type: &str) -> [BatterySource; 2] {
+    let (family, major) = product_type
+        .split_once(',')
+        .map(|(prefix, _)| {
+            let digits = prefix.trim_start_matches(|c: char| !c.is_ascii_digit());
+            (
+                &prefix[..prefix.len() - digits.len()],
+                digits.parse::<u8>().unwrap_or_default(),
+            )
+        })
+        .unwrap_or_default();
+
+    // up to the iPhone 7 generation the battery values usually sit on the PMU charger entry
+    let older = match family {
+        "iPhone" => major <= 9,
+        "iPad" => major <= 7,
+        "iPod" => true,
+        _ => false,
     };
 
+    if older {
+        [
+            BatterySource::AppleARMPMUCharger,
+            BatterySource::AppleSmartBattery,
+        ]
+    } else {
+        [
+            BatterySource::AppleSmartBattery,
+            BatterySource::AppleARMPMUCharger,
+        ]
+    }
+}
+
+/// the first entry that actually has battery values, by name and then by class. an entry that
+/// fails to read or has no `CurrentCapacity` just moves on to the next one
+async fn get_battery_plist(
+    provider: &DeviceProvider,
+    device_diag: &mut DiagnosticsRelayClient,
+) -> Result<(BatterySource, plist::Dictionary), String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
     let model = get_string_value_or_default(&mut lockdownd_client, Some("ProductType"), None)
         .await
-        .unwrap_or("Unknown".into());
-
-    let model_version = model.trim_start_matches("iPhone");
-    let model_version_first_num = model_version
-        .split_once(',')
-        .map(|(code, _)| code.parse::<u8>().unwrap_or_default())
         .unwrap_or_default();
 
-    if model_version_first_num <= 9 {
-        // only to iPhone 7 and earlier
-        Ok("AppleARMPMUCharger".into())
-    } else {
-        Ok("AppleSmartBattery".into())
+    let mut last_error = None;
+
+    for source in battery_source_order(&model) {
+        for (name, class) in [(Some(source.as_str()), None), (None, Some(source.as_str()))] {
+            match device_diag.ioregistry(None, name, class).await {
+                Ok(Some(battery_plist)) if battery_plist.rfind("CurrentCapacity").is_some() => {
+                    return Ok((source, battery_plist));
+                }
+                Ok(_) => {}
+                Err(e) => last_error = Some(format!("{}: {e:?}", source.as_str())),
+            }
+        }
     }
+
+    Err(match last_error {
+        Some(e) => format!("found no battery in the ioregistry, the last query failed with {e}"),
+        None => format!("{model} has no battery in its ioregistry"),
+    })
 }
 
 #[repr(C)]
@@ -229,7 +493,19 @@ pub struct Hardware {
     pub region: CString,
 }
 
-pub async fn handle_device_hardware(provider: &UsbmuxdProvider) -> Result<Hardware, String> {
+/// the product type, and the model number and region lockdownd reports decoded into their
+/// parts
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HardwareInfo {
+    /// like `iPhone14,2`
+    pub product_type: String,
+    #[serde(flatten)]
+    pub model_number: ModelNumberInfo,
+}
+
+pub async fn handle_device_hardware_info(
+    provider: &DeviceProvider,
+) -> Result<HardwareInfo, String> {
     let mut lockdownd_client = match get_lockdownd_client(provider).await {
         Ok(lockdown) => lockdown,
         Err(e) => {
@@ -243,47 +519,179 @@ pub async fn handle_device_hardware(provider: &UsbmuxdProvider) -> Result<Hardwa
         .await
         .unwrap_or_default();
 
-    let region: String = match region_code.as_str() {
-        "LL/A" => "United States".into(),
-        "B/A" => "Canada".into(),
-        "C/A" => "Europe".into(),
-        "J/A" => "Japan".into(),
-        "X/A" => "China".into(),
-        "ZP/A" => "Global/International".into(),
-        "KH/A" => "Hong Kong".into(),
-        "M/A" => "Mexico".into(),
-        "A/A" => "Argentina".into(),
-        "T/A" => "Taiwan".into(),
-        "V/A" => "United Kingdom".into(),
-        "R/A" => "Russia".into(),
-        _ => "unknown".into(),
-    };
-
-    let model_number_code =
+    let model_number =
         get_string_value_or_default(&mut lockdownd_client, Some("ModelNumber"), None)
             .await
             .unwrap_or_default();
 
-    let model_meaning = match model_number_code.chars().next().unwrap_or_default() {
-        'F' => "Refurbished Device",
-        'M' => "New Device",
-        'N' => "Warranty Replacement Device",
-        'P' => "Personalized Device",
-        '3' => "Demo Device",
-        _ => "Unknown",
+    let product_type =
+        get_string_value_or_default(&mut lockdownd_client, Some("ProductType"), None)
+            .await
+            .unwrap_or("Unknown".into());
+
+    Ok(HardwareInfo {
+        product_type,
+        model_number: decode_model_number(&model_number, &region_code),
+    })
+}
+
+pub async fn handle_device_hardware(provider: &DeviceProvider) -> Result<Hardware, String> {
+    let info = handle_device_hardware_info(provider).await?;
+    let decoded = info.model_number;
+
+    let model_number = format!(
+        "{} ({})",
+        decoded.model_number, decoded.purchase_type_description
+    );
+
+    Ok(Hardware {
+        model: CString::new(info.product_type).unwrap_or_default(),
+        model_number: CString::new(model_number).unwrap_or_default(),
+        region: CString::new(decoded.region_name.unwrap_or("unknown")).unwrap_or_default(),
+    })
+}
+
+/// the identifiers lockdownd knows, `None` for the ones the device doesn't have or hides
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeviceIdentity {
+    pub serial_number: Option<String>,
+    pub udid: Option<String>,
+    pub ecid: Option<u64>,
+    /// the ecid the way restore tools print it, like `0x1A2B3C4D5E6F7`
+    pub ecid_hex: Option<String>,
+    pub imei: Option<String>,
+    /// the second SIM's imei on dual SIM devices
+    pub imei2: Option<String>,
+    pub meid: Option<String>,
+    pub phone_number: Option<String>,
+    pub wifi_address: Option<String>,
+    pub bluetooth_address: Option<String>,
+    pub baseband_version: Option<String>,
+}
+
+pub async fn handle_device_identity(provider: &DeviceProvider) -> Result<DeviceIdentity, String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
+    // one request for every value instead of one per key
+    let values = lockdownd_client
+        .get_value(None, None)
+        .await
+        .map_err(|e| format!("failed to read the device values: {e:?}"))?
+        .into_dictionary()
+        .unwrap_or_default();
+
+    let string = |key: &str| {
+        values
+            .get(key)
+            .and_then(Value::as_string)
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
     };
 
-    let model_number = format!("{model_number_code} ({model_meaning})",);
+    let ecid = values
+        .get("UniqueChipID")
+        .and_then(Value::as_unsigned_integer);
+
+    Ok(DeviceIdentity {
+        serial_number: string("SerialNumber"),
+        udid: string("UniqueDeviceID"),
+        ecid,
+        ecid_hex: ecid.map(|e| format!("0x{e:X}")),
+        imei: string("InternationalMobileEquipmentIdentity"),
+        imei2: string("InternationalMobileEquipmentIdentity2"),
+        meid: string("MobileEquipmentIdentifier"),
+        phone_number: string("PhoneNumber"),
+        wifi_address: string("WiFiAddress"),
+        bluetooth_address: string("BluetoothAddress"),
+        baseband_version: string("BasebandVersion"),
+    })
+}
 
-    let model = get_string_value_or_default(&mut lockdownd_client, Some("ProductType"), None)
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DeviceStatus {
+    /// like `Activated`, `Unactivated` or `FactoryActivated`
+    pub activation_state: Option<String>,
+    pub passcode_protected: Option<bool>,
+    pub supervised: Option<bool>,
+    /// only visible on some iOS versions, `None` means we couldn't tell
+    pub find_my_enabled: Option<bool>,
+    /// `None` before iOS 16, it didn't exist
+    pub developer_mode: Option<bool>,
+    /// nothing in `blockers`. values we couldn't read don't block, except the activation state,
+    /// which has to be one we know
+    pub ready_to_ship: bool,
+    /// why the device shouldn't leave yet
+    pub blockers: Vec<String>,
+}
+
+async fn get_bool_value(
+    lockdownd_client: &mut LockdownClient,
+    key: &str,
+    domain: Option<&str>,
+) -> Option<bool> {
+    lockdownd_client
+        .get_value(Some(key), domain)
         .await
-        .unwrap_or("Unknown".into());
+        .ok()
+        .and_then(|v| v.as_boolean())
+}
+
+pub async fn handle_device_status(provider: &DeviceProvider) -> Result<DeviceStatus, String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
+    let activation_state =
+        get_string_value_or_default(&mut lockdownd_client, Some("ActivationState"), None)
+            .await
+            .filter(|s| !s.is_empty());
+    let passcode_protected = get_bool_value(&mut lockdownd_client, "PasswordProtected", None).await;
+    let supervised = get_bool_value(
+        &mut lockdownd_client,
+        "DeviceIsChaperoned",
+        Some("com.apple.mobile.chaperone"),
+    )
+    .await;
+    let find_my_enabled = get_bool_value(
+        &mut lockdownd_client,
+        "IsAssociated",
+        Some("com.apple.fmip"),
+    )
+    .await;
+    let developer_mode = get_bool_value(
+        &mut lockdownd_client,
+        "DeveloperModeStatus",
+        Some("com.apple.security.mac.amfi"),
+    )
+    .await;
+
+    let mut blockers = Vec::new();
+    match activation_state.as_deref() {
+        // a wiped device is unactivated, that's fine to ship
+        Some("Activated" | "FactoryActivated" | "Unactivated") => {}
+        Some(state) => blockers.push(format!("the activation state is {state}")),
+        None => blockers.push("the activation state is unknown".to_string()),
+    }
+    if passcode_protected == Some(true) {
+        blockers.push("a passcode is set".to_string());
+    }
+    if find_my_enabled == Some(true) {
+        blockers.push("Find My is on, the device is activation locked".to_string());
+    }
+    if supervised == Some(true) {
+        blockers.push("the device is supervised".to_string());
+    }
+    if developer_mode == Some(true) {
+        blockers.push("developer mode is on".to_string());
+    }
 
-    Ok(dbg!(Hardware {
-        model: CString::new(model).unwrap(),
-        model_number: CString::new(model_number).unwrap(),
-        region: CString::new(region).unwrap(),
-    }))
+    Ok(DeviceStatus {
+        activation_state,
+        passcode_protected,
+        supervised,
+        find_my_enabled,
+        developer_mode,
+        ready_to_ship: blockers.is_empty(),
+        blockers,
+    })
 }
 
 #[derive(Clone, Debug, Default)]
@@ -299,7 +707,7 @@ pub struct RawOS {
     pub build_num: *mut c_char,
 }
 
-pub async fn handle_device_os(provider: &UsbmuxdProvider) -> Result<OS, String> {
+pub async fn handle_device_os(provider: &DeviceProvider) -> Result<OS, String> {
     let mut lockdownd_client = match get_lockdownd_client(provider).await {
         Ok(lockdown) => lockdown,
         Err(e) => {
@@ -0,0 +1,320 @@
+use std::{
+    fs::File,
+    io::{Read, Seek},
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+use plist::{Dictionary, Value};
+use serde::Serialize;
+use zip::ZipArchive;
+
+use crate::idevice_helper::get_lockdownd_client;
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::{compare_versions, get_string_value_or_default};
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ProvisioningProfile {
+    pub name: Option<String>,
+    pub team_id: Option<String>,
+    pub team_name: Option<String>,
+    pub expiration: Option<String>,
+    pub expired: bool,
+    pub provisions_all_devices: bool,
+    pub provisioned_devices: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct IpaInfo {
+    pub bundle_id: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub build: Option<String>,
+    pub minimum_os: Option<String>,
+    pub device_families: Vec<String>,
+    /// `None` for app store builds, they don't embed a profile
+    pub profile: Option<ProvisioningProfile>,
+    /// reasons the app won't install on the connected device, empty when no device was checked
+    pub warnings: Vec<String>,
+}
+
+fn device_family_name(family: u64) -> String {
+    match family {
+        1 => "iPhone".into(),
+        2 => "iPad".into(),
+        3 => "AppleTV".into(),
+        4 => "AppleWatch".into(),
+        6 => "Mac".into(),
+        7 => "AppleVision".into(),
+        other => format!("Unknown({other})"),
+    }
+}
+
+/// the path of `file` inside `Payload/<name>.app/`
+fn find_app_file<R: Read + Seek>(archive: &ZipArchive<R>, file: &str) -> Option<String> {
+    archive
+        .file_names()
+        .find(|name| {
+            let parts = name.split('/').collect::<Vec<_>>();
+            parts.len() == 3
+                && parts[0] == "Payload"
+                && parts[1].ends_with(".app")
+                && parts[2] == file
+        })
+        .map(ToString::to_string)
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|e| format!("failed to open {name} in the ipa: {e:?}"))?;
+
+    let mut data = Vec::with_capacity(entry.size() as usize);
+    entry
+        .read_to_end(&mut data)
+        .map_err(|e| format!("failed to read {name} in the ipa: {e:?}"))?;
+
+    Ok(data)
+}
+
+/// the profile is a signed CMS blob, the plist sits unencrypted in the middle of it
+fn parse_profile(data: &[u8]) -> Result<ProvisioningProfile, String> {
+    let start = data
+        .windows(5)
+        .position(|w| w == b"<?xml")
+        .ok_or("the provisioning profile has no plist")?;
+    let end = data[start..]
+        .windows(8)
+        .position(|w| w == b"</plist>")
+        .map(|p| start + p + 8)
+        .ok_or("the provisioning profile plist is truncated")?;
+
+    let profile: Dictionary = plist::from_bytes(&data[start..end])
+        .map_err(|e| format!("failed to parse the provisioning profile: {e:?}"))?;
+
+    let string = |key: &str| {
+        profile
+            .get(key)
+            .and_then(Value::as_string)
+            .map(ToString::to_string)
+    };
+
+    let expiration = profile
+        .get("ExpirationDate")
+        .and_then(Value::as_date)
+        .map(|d| DateTime::<Utc>::from(std::time::SystemTime::from(d)));
+
+    Ok(ProvisioningProfile {
+        name: string("Name"),
+        team_id: profile
+            .get("TeamIdentifier")
+            .and_then(Value::as_array)
+            .and_then(|ids| ids.first())
+            .and_then(Value::as_string)
+            .map(ToString::to_string),
+        team_name: string("TeamName"),
+        expired: expiration.is_some_and(|e| e < Utc::now()),
+        expiration: expiration.map(|e| e.to_rfc3339()),
+        provisions_all_devices: profile
+            .get("ProvisionsAllDevices")
+            .and_then(Value::as_boolean)
+            .unwrap_or_default(),
+        provisioned_devices: profile
+            .get("ProvisionedDevices")
+            .and_then(Value::as_array)
+            .map(|devices| {
+                devices
+                    .iter()
+                    .filter_map(Value::as_string)
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// reads the app's `Info.plist` and provisioning profile on the host without touching a device
+pub fn read_ipa(path: impl AsRef<Path>) -> Result<IpaInfo, String> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| format!("failed to open {}: {e:?}", path.display()))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| format!("{} is not a valid ipa: {e:?}", path.display()))?;
+
+    let info_plist_path =
+        find_app_file(&archive, "Info.plist").ok_or("the ipa has no Payload/*.app/Info.plist")?;
+    let info: Dictionary = plist::from_bytes(&read_entry(&mut archive, &info_plist_path)?)
+        .map_err(|e| format!("failed to parse the app's Info.plist: {e:?}"))?;
+
+    let string = |key: &str| {
+        info.get(key)
+            .and_then(Value::as_string)
+            .map(ToString::to_string)
+    };
+
+    let profile = match find_app_file(&archive, "embedded.mobileprovision") {
+        Some(name) => Some(parse_profile(&read_entry(&mut archive, &name)?)?),
+        None => None,
+    };
+
+    Ok(IpaInfo {
+        bundle_id: string("CFBundleIdentifier").unwrap_or_default(),
+        name: string("CFBundleDisplayName").or_else(|| string("CFBundleName")),
+        version: string("CFBundleShortVersionString"),
+        build: string("CFBundleVersion"),
+        minimum_os: string("MinimumOSVersion"),
+        device_families: info
+            .get("UIDeviceFamily")
+            .and_then(Value::as_array)
+            .map(|families| {
+                families
+                    .iter()
+                    .filter_map(Value::as_unsigned_integer)
+                    .map(device_family_name)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        profile,
+        warnings: Vec::new(),
+    })
+}
+
+/// fills `ipa.warnings` with everything that would stop it from installing on the device
+pub async fn check_compatibility(
+    provider: &DeviceProvider,
+    ipa: &mut IpaInfo,
+) -> Result<(), String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
+    let ios_ver = get_string_value_or_default(&mut lockdownd_client, Some("ProductVersion"), None)
+        .await
+        .filter(|v| !v.is_empty());
+    let udid = get_string_value_or_default(&mut lockdownd_client, Some("UniqueDeviceID"), None)
+        .await
+        .unwrap_or_default();
+    let device_class =
+        get_string_value_or_default(&mut lockdownd_client, Some("DeviceClass"), None)
+            .await
+            .unwrap_or_default();
+
+    if let Some(minimum_os) = &ipa.minimum_os {
+        match &ios_ver {
+            Some(ios_ver) if compare_versions(ios_ver, minimum_os).is_lt() => {
+                ipa.warnings.push(format!(
+                    "the app needs iOS {minimum_os} but the device runs {ios_ver}"
+                ));
+            }
+            Some(_) => {}
+            None => ipa.warnings.push(format!(
+                "the app needs iOS {minimum_os} but the device runs an unknown iOS version"
+            )),
+        }
+    }
+
+    // iPods run iPhone apps and iPads run them in compatibility mode
+    let runs: &[&str] = match device_class.as_str() {
+        "iPhone" | "iPod" => &["iPhone"],
+        "iPad" => &["iPad", "iPhone"],
+        _ => &[],
+    };
+    if !runs.is_empty()
+        && !ipa.device_families.is_empty()
+        && !ipa
+            .device_families
+            .iter()
+            .any(|f| runs.contains(&f.as_str()))
+    {
+        ipa.warnings.push(format!(
+            "the app supports {} but the device is an {device_class}",
+            ipa.device_families.join(", ")
+        ));
+    }
+
+    if let Some(profile) = &ipa.profile {
+        if profile.expired {
+            ipa.warnings
+                .push("the embedded provisioning profile has expired".into());
+        }
+
+        if !profile.provisions_all_devices
+            && !profile.provisioned_devices.is_empty()
+            && !profile.provisioned_devices.contains(&udid)
+        {
+            ipa.warnings.push(format!(
+                "the device {udid} is not in the provisioning profile"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+
+    use zip::{ZipWriter, write::SimpleFileOptions};
+
+    use super::*;
+
+    const PROFILE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+    <key>Name</key><string>Test Profile</string>
+    <key>TeamIdentifier</key><array><string>ABCDE12345</string></array>
+    <key>TeamName</key><string>Test Team</string>
+    <key>ExpirationDate</key><date>2001-01-01T00:00:00Z</date>
+    <key>ProvisionedDevices</key><array><string>00008030-0001</string></array>
+</dict>
+</plist>"#;
+
+    #[test]
+    fn parse_profile_reads_the_plist_inside_the_signature() {
+        // the cms envelope around the plist is binary, only the plist slice gets parsed
+        let mut data = b"\x30\x82\x1f\x00 signed data ".to_vec();
+        data.extend_from_slice(PROFILE.as_bytes());
+        data.extend_from_slice(b"\x00\xa0\x82 signer info");
+
+        let profile = parse_profile(&data).unwrap();
+
+        assert_eq!(profile.name.as_deref(), Some("Test Profile"));
+        assert_eq!(profile.team_id.as_deref(), Some("ABCDE12345"));
+        assert_eq!(profile.team_name.as_deref(), Some("Test Team"));
+        assert!(profile.expired);
+        assert!(!profile.provisions_all_devices);
+        assert_eq!(profile.provisioned_devices, ["00008030-0001"]);
+    }
+
+    #[test]
+    fn parse_profile_rejects_a_missing_or_unterminated_plist() {
+        assert!(parse_profile(b"\x30\x82 no plist in here").is_err());
+
+        let truncated = &PROFILE[..PROFILE.len() - "</plist>".len()];
+        assert!(parse_profile(truncated.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn find_app_file_only_looks_at_the_top_of_the_app() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in [
+            "Payload/Test.app/Frameworks/Kit.framework/Info.plist",
+            "Payload/Test.app/Info.plist",
+            "Payload/Test.app/embedded.mobileprovision",
+        ] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"-").unwrap();
+        }
+        let archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        assert_eq!(
+            find_app_file(&archive, "Info.plist").as_deref(),
+            Some("Payload/Test.app/Info.plist")
+        );
+        assert_eq!(
+            find_app_file(&archive, "embedded.mobileprovision").as_deref(),
+            Some("Payload/Test.app/embedded.mobileprovision")
+        );
+        assert_eq!(find_app_file(&archive, "Kit.framework"), None);
+    }
+}
@@ -0,0 +1,239 @@
+use serde::Serialize;
+
+use crate::idevice_helper::get_lockdownd_client;
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::get_string_value_or_default;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DeviceModel {
+    /// what lockdownd reports as `ProductType`, like `iPhone14,2`
+    pub product_type: &'static str,
+    /// what lockdownd reports as `HardwareModel`, like `D63AP`
+    pub board_ids: &'static [&'static str],
+    pub name: &'static str,
+    pub year: u16,
+    pub chip: &'static str,
+    /// `None` while the model still gets the latest iOS
+    pub max_ios: Option<&'static str>,
+}
+
+const fn model(
+    product_type: &'static str,
+    board_ids: &'static [&'static str],
+    name: &'static str,
+    year: u16,
+    chip: &'static str,
+    max_ios: Option<&'static str>,
+) -> DeviceModel {
+    DeviceModel {
+        product_type,
+        board_ids,
+        name,
+        year,
+        chip,
+        max_ios,
+    }
+}
+
+#[rustfmt::skip]
+static MODELS: &[DeviceModel] = &[
+    model("iPhone6,1", &["N51AP"], "iPhone 5s", 2013, "A7", Some("12.5.7")),
+    model("iPhone6,2", &["N53AP"], "iPhone 5s", 2013, "A7", Some("12.5.7")),
+    model("iPhone7,2", &["N61AP"], "iPhone 6", 2014, "A8", Some("12.5.7")),
+    model("iPhone7,1", &["N56AP"], "iPhone 6 Plus", 2014, "A8", Some("12.5.7")),
+    model("iPhone8,1", &["N71AP", "N71mAP"], "iPhone 6s", 2015, "A9", Some("15.8")),
+    model("iPhone8,2", &["N66AP", "N66mAP"], "iPhone 6s Plus", 2015, "A9", Some("15.8")),
+    model("iPhone8,4", &["N69AP", "N69uAP"], "iPhone SE (1st generation)", 2016, "A9", Some("15.8")),
+    model("iPhone9,1", &["D10AP"], "iPhone 7", 2016, "A10 Fusion", Some("15.8")),
+    model("iPhone9,3", &["D101AP"], "iPhone 7", 2016, "A10 Fusion", Some("15.8")),
+    model("iPhone9,2", &["D11AP"], "iPhone 7 Plus", 2016, "A10 Fusion", Some("15.8")),
+    model("iPhone9,4", &["D111AP"], "iPhone 7 Plus", 2016, "A10 Fusion", Some("15.8")),
+    model("iPhone10,1", &["D20AP"], "iPhone 8", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone10,4", &["D201AP"], "iPhone 8", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone10,2", &["D21AP"], "iPhone 8 Plus", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone10,5", &["D211AP"], "iPhone 8 Plus", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone10,3", &["D22AP"], "iPhone X", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone10,6", &["D221AP"], "iPhone X", 2017, "A11 Bionic", Some("16.7")),
+    model("iPhone11,2", &["D321AP"], "iPhone XS", 2018, "A12 Bionic", Some("18")),
+    model("iPhone11,4", &["D331AP"], "iPhone XS Max", 2018, "A12 Bionic", Some("18")),
+    model("iPhone11,6", &["D331pAP"], "iPhone XS Max", 2018, "A12 Bionic", Some("18")),
+    model("iPhone11,8", &["N841AP"], "iPhone XR", 2018, "A12 Bionic", Some("18")),
+    model("iPhone12,1", &["N104AP"], "iPhone 11", 2019, "A13 Bionic", None),
+    model("iPhone12,3", &["D421AP"], "iPhone 11 Pro", 2019, "A13 Bionic", None),
+    model("iPhone12,5", &["D431AP"], "iPhone 11 Pro Max", 2019, "A13 Bionic", None),
+    model("iPhone12,8", &["D79AP"], "iPhone SE (2nd generation)", 2020, "A13 Bionic", None),
+    model("iPhone13,1", &["D52gAP"], "iPhone 12 mini", 2020, "A14 Bionic", None),
+    model("iPhone13,2", &["D53gAP"], "iPhone 12", 2020, "A14 Bionic", None),
+    model("iPhone13,3", &["D53pAP"], "iPhone 12 Pro", 2020, "A14 Bionic", None),
+    model("iPhone13,4", &["D54pAP"], "iPhone 12 Pro Max", 2020, "A14 Bionic", None),
+    model("iPhone14,4", &["D16AP"], "iPhone 13 mini", 2021, "A15 Bionic", None),
+    model("iPhone14,5", &["D17AP"], "iPhone 13", 2021, "A15 Bionic", None),
+    model("iPhone14,2", &["D63AP"], "iPhone 13 Pro", 2021, "A15 Bionic", None),
+    model("iPhone14,3", &["D64AP"], "iPhone 13 Pro Max", 2021, "A15 Bionic", None),
+    model("iPhone14,6", &["D49AP"], "iPhone SE (3rd generation)", 2022, "A15 Bionic", None),
+    model("iPhone14,7", &["D27AP"], "iPhone 14", 2022, "A15 Bionic", None),
+    model("iPhone14,8", &["D28AP"], "iPhone 14 Plus", 2022, "A15 Bionic", None),
+    model("iPhone15,2", &["D73AP"], "iPhone 14 Pro", 2022, "A16 Bionic", None),
+    model("iPhone15,3", &["D74AP"], "iPhone 14 Pro Max", 2022, "A16 Bionic", None),
+    model("iPhone15,4", &["D37AP"], "iPhone 15", 2023, "A16 Bionic", None),
+    model("iPhone15,5", &["D38AP"], "iPhone 15 Plus", 2023, "A16 Bionic", None),
+    model("iPhone16,1", &["D83AP"], "iPhone 15 Pro", 2023, "A17 Pro", None),
+    model("iPhone16,2", &["D84AP"], "iPhone 15 Pro Max", 2023, "A17 Pro", None),
+    model("iPhone17,3", &["D47AP"], "iPhone 16", 2024, "A18", None),
+    model("iPhone17,4", &["D48AP"], "iPhone 16 Plus", 2024, "A18", None),
+    model("iPhone17,1", &["D93AP"], "iPhone 16 Pro", 2024, "A18 Pro", None),
+    model("iPhone17,2", &["D94AP"], "iPhone 16 Pro Max", 2024, "A18 Pro", None),
+    model("iPhone17,5", &["V59AP"], "iPhone 16e", 2025, "A18", None),
+    // known incomplete: the board ids of the iPhone 17 family aren't known yet, so these are
+    // only found by product type
+    model("iPhone18,3", &[], "iPhone 17", 2025, "A19", None),
+    model("iPhone18,4", &[], "iPhone Air", 2025, "A19 Pro", None),
+    model("iPhone18,1", &[], "iPhone 17 Pro", 2025, "A19 Pro", None),
+    model("iPhone18,2", &[], "iPhone 17 Pro Max", 2025, "A19 Pro", None),
+    model("iPod7,1", &["N102AP"], "iPod touch (6th generation)", 2015, "A8", Some("12.5.7")),
+    model("iPod9,1", &["N112AP"], "iPod touch (7th generation)", 2019, "A10 Fusion", Some("15.8")),
+    model("iPad6,11", &["J71sAP"], "iPad (5th generation)", 2017, "A9", Some("16.7")),
+    model("iPad6,12", &["J72sAP"], "iPad (5th generation)", 2017, "A9", Some("16.7")),
+    model("iPad7,5", &["J71bAP"], "iPad (6th generation)", 2018, "A10 Fusion", Some("17")),
+    model("iPad7,6", &["J72bAP"], "iPad (6th generation)", 2018, "A10 Fusion", Some("17")),
+    model("iPad7,11", &["J171AP"], "iPad (7th generation)", 2019, "A10 Fusion", Some("18")),
+    model("iPad7,12", &["J172AP"], "iPad (7th generation)", 2019, "A10 Fusion", Some("18")),
+    model("iPad11,6", &["J171aAP"], "iPad (8th generation)", 2020, "A12 Bionic", None),
+    model("iPad11,7", &["J172aAP"], "iPad (8th generation)", 2020, "A12 Bionic", None),
+    model("iPad12,1", &["J181AP"], "iPad (9th generation)", 2021, "A13 Bionic", None),
+    model("iPad12,2", &["J182AP"], "iPad (9th generation)", 2021, "A13 Bionic", None),
+    model("iPad13,18", &["J271AP"], "iPad (10th generation)", 2022, "A14 Bionic", None),
+    model("iPad13,19", &["J272AP"], "iPad (10th generation)", 2022, "A14 Bionic", None),
+    model("iPad15,7", &["J481AP"], "iPad (A16)", 2025, "A16", None),
+    model("iPad15,8", &["J482AP"], "iPad (A16)", 2025, "A16", None),
+    model("iPad11,3", &["J217AP"], "iPad Air (3rd generation)", 2019, "A12 Bionic", None),
+    model("iPad11,4", &["J218AP"], "iPad Air (3rd generation)", 2019, "A12 Bionic", None),
+    model("iPad13,1", &["J307AP"], "iPad Air (4th generation)", 2020, "A14 Bionic", None),
+    model("iPad13,2", &["J308AP"], "iPad Air (4th generation)", 2020, "A14 Bionic", None),
+    model("iPad13,16", &["J407AP"], "iPad Air (5th generation)", 2022, "M1", None),
+    model("iPad13,17", &["J408AP"], "iPad Air (5th generation)", 2022, "M1", None),
+    model("iPad14,8", &["J507AP"], "iPad Air 11-inch (M2)", 2024, "M2", None),
+    model("iPad14,9", &["J508AP"], "iPad Air 11-inch (M2)", 2024, "M2", None),
+    model("iPad14,10", &["J537AP"], "iPad Air 13-inch (M2)", 2024, "M2", None),
+    model("iPad14,11", &["J538AP"], "iPad Air 13-inch (M2)", 2024, "M2", None),
+    model("iPad15,3", &["J607AP"], "iPad Air 11-inch (M3)", 2025, "M3", None),
+    model("iPad15,4", &["J608AP"], "iPad Air 11-inch (M3)", 2025, "M3", None),
+    model("iPad15,5", &["J637AP"], "iPad Air 13-inch (M3)", 2025, "M3", None),
+    model("iPad15,6", &["J638AP"], "iPad Air 13-inch (M3)", 2025, "M3", None),
+    model("iPad11,1", &["J210AP"], "iPad mini (5th generation)", 2019, "A12 Bionic", None),
+    model("iPad11,2", &["J211AP"], "iPad mini (5th generation)", 2019, "A12 Bionic", None),
+    model("iPad14,1", &["J310AP"], "iPad mini (6th generation)", 2021, "A15 Bionic", None),
+    model("iPad14,2", &["J311AP"], "iPad mini (6th generation)", 2021, "A15 Bionic", None),
+    model("iPad16,1", &["J410AP"], "iPad mini (A17 Pro)", 2024, "A17 Pro", None),
+    model("iPad16,2", &["J411AP"], "iPad mini (A17 Pro)", 2024, "A17 Pro", None),
+    model("iPad6,3", &["J127AP"], "iPad Pro (9.7-inch)", 2016, "A9X", Some("16.7")),
+    model("iPad6,4", &["J128AP"], "iPad Pro (9.7-inch)", 2016, "A9X", Some("16.7")),
+    model("iPad6,7", &["J98aAP"], "iPad Pro (12.9-inch) (1st generation)", 2015, "A9X", Some("16.7")),
+    model("iPad6,8", &["J99aAP"], "iPad Pro (12.9-inch) (1st generation)", 2015, "A9X", Some("16.7")),
+    model("iPad7,1", &["J120AP"], "iPad Pro (12.9-inch) (2nd generation)", 2017, "A10X Fusion", Some("17")),
+    model("iPad7,2", &["J121AP"], "iPad Pro (12.9-inch) (2nd generation)", 2017, "A10X Fusion", Some("17")),
+    model("iPad7,3", &["J207AP"], "iPad Pro (10.5-inch)", 2017, "A10X Fusion", Some("17")),
+    model("iPad7,4", &["J208AP"], "iPad Pro (10.5-inch)", 2017, "A10X Fusion", Some("17")),
+    model("iPad8,1", &["J317AP"], "iPad Pro (11-inch) (1st generation)", 2018, "A12X Bionic", None),
+    model("iPad8,2", &["J317xAP"], "iPad Pro (11-inch) (1st generation)", 2018, "A12X Bionic", None),
+    model("iPad8,3", &["J318AP"], "iPad Pro (11-inch) (1st generation)", 2018, "A12X Bionic", None),
+    model("iPad8,4", &["J318xAP"], "iPad Pro (11-inch) (1st generation)", 2018, "A12X Bionic", None),
+    model("iPad8,5", &["J320AP"], "iPad Pro (12.9-inch) (3rd generation)", 2018, "A12X Bionic", None),
+    model("iPad8,6", &["J320xAP"], "iPad Pro (12.9-inch) (3rd generation)", 2018, "A12X Bionic", None),
+    model("iPad8,7", &["J321AP"], "iPad Pro (12.9-inch) (3rd generation)", 2018, "A12X Bionic", None),
+    model("iPad8,8", &["J321xAP"], "iPad Pro (12.9-inch) (3rd generation)", 2018, "A12X Bionic", None),
+    model("iPad8,9", &["J417AP"], "iPad Pro (11-inch) (2nd generation)", 2020, "A12Z Bionic", None),
+    model("iPad8,10", &["J418AP"], "iPad Pro (11-inch) (2nd generation)", 2020, "A12Z Bionic", None),
+    model("iPad8,11", &["J420AP"], "iPad Pro (12.9-inch) (4th generation)", 2020, "A12Z Bionic", None),
+    model("iPad8,12", &["J421AP"], "iPad Pro (12.9-inch) (4th generation)", 2020, "A12Z Bionic", None),
+    model("iPad13,4", &["J517AP"], "iPad Pro (11-inch) (3rd generation)", 2021, "M1", None),
+    model("iPad13,5", &["J517xAP"], "iPad Pro (11-inch) (3rd generation)", 2021, "M1", None),
+    model("iPad13,6", &["J518AP"], "iPad Pro (11-inch) (3rd generation)", 2021, "M1", None),
+    model("iPad13,7", &["J518xAP"], "iPad Pro (11-inch) (3rd generation)", 2021, "M1", None),
+    model("iPad13,8", &["J522AP"], "iPad Pro (12.9-inch) (5th generation)", 2021, "M1", None),
+    model("iPad13,9", &["J522xAP"], "iPad Pro (12.9-inch) (5th generation)", 2021, "M1", None),
+    model("iPad13,10", &["J523AP"], "iPad Pro (12.9-inch) (5th generation)", 2021, "M1", None),
+    model("iPad13,11", &["J523xAP"], "iPad Pro (12.9-inch) (5th generation)", 2021, "M1", None),
+    model("iPad14,3", &["J617AP"], "iPad Pro (11-inch) (4th generation)", 2022, "M2", None),
+    model("iPad14,4", &["J618AP"], "iPad Pro (11-inch) (4th generation)", 2022, "M2", None),
+    model("iPad14,5", &["J620AP"], "iPad Pro (12.9-inch) (6th generation)", 2022, "M2", None),
+    model("iPad14,6", &["J621AP"], "iPad Pro (12.9-inch) (6th generation)", 2022, "M2", None),
+    model("iPad16,3", &["J717AP"], "iPad Pro 11-inch (M4)", 2024, "M4", None),
+    model("iPad16,4", &["J718AP"], "iPad Pro 11-inch (M4)", 2024, "M4", None),
+    model("iPad16,5", &["J720AP"], "iPad Pro 13-inch (M4)", 2024, "M4", None),
+    model("iPad16,6", &["J721AP"], "iPad Pro 13-inch (M4)", 2024, "M4", None),
+];
+
+/// finds a model by its product type or board id, both ignoring case
+pub fn lookup_model(id: &str) -> Option<&'static DeviceModel> {
+    let id = id.trim();
+
+    MODELS.iter().find(|m| {
+        m.product_type.eq_ignore_ascii_case(id)
+            || m.board_ids.iter().any(|b| b.eq_ignore_ascii_case(id))
+    })
+}
+
+/// the model of the connected device, by its product type and then its board id
+pub async fn get_device_model(
+    provider: &DeviceProvider,
+) -> Result<Option<&'static DeviceModel>, String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
+    let product_type =
+        get_string_value_or_default(&mut lockdownd_client, Some("ProductType"), None)
+            .await
+            .unwrap_or_default();
+
+    if let Some(model) = lookup_model(&product_type) {
+        return Ok(Some(model));
+    }
+
+    let board_id = get_string_value_or_default(&mut lockdownd_client, Some("HardwareModel"), None)
+        .await
+        .unwrap_or_default();
+
+    Ok(lookup_model(&board_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_model_finds_product_types_and_board_ids_ignoring_case() {
+        assert_eq!(
+            lookup_model("iPhone14,2").map(|m| m.name),
+            Some("iPhone 13 Pro")
+        );
+        assert_eq!(
+            lookup_model(" iphone14,2 ").map(|m| m.name),
+            Some("iPhone 13 Pro")
+        );
+        assert_eq!(
+            lookup_model("d63ap").map(|m| m.product_type),
+            Some("iPhone14,2")
+        );
+        assert_eq!(lookup_model("iPad15,5").map(|m| m.chip), Some("M3"));
+    }
+
+    #[test]
+    fn lookup_model_knows_nothing_about_unknown_ids() {
+        assert!(lookup_model("iPhone99,1").is_none());
+        assert!(lookup_model("X99AP").is_none());
+        assert!(lookup_model("").is_none());
+    }
+
+    #[test]
+    fn models_are_listed_once() {
+        for (i, m) in MODELS.iter().enumerate() {
+            assert!(
+                MODELS[i + 1..]
+                    .iter()
+                    .all(|o| o.product_type != m.product_type),
+                "{} is listed twice",
+                m.product_type
+            );
+        }
+    }
+}
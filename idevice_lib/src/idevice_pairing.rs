@@ -0,0 +1,333 @@
+use std::{error::Error, str::FromStr, time::Duration};
+
+use idevice::{
+    IdeviceService,
+    lockdown::LockdownClient,
+    pairing_file::PairingFile,
+    provider::IdeviceProvider,
+    usbmuxd::{UsbmuxdAddr, UsbmuxdConnection},
+};
+use plist::{Dictionary, Value};
+use rsa::{
+    RsaPrivateKey, RsaPublicKey,
+    pkcs1::DecodeRsaPublicKey,
+    pkcs1v15::SigningKey,
+    pkcs8::{EncodePrivateKey, LineEnding, SubjectPublicKeyInfo},
+};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    time::{sleep, timeout},
+};
+use x509_cert::{
+    builder::{Builder, CertificateBuilder, Profile},
+    der::EncodePem,
+    name::Name,
+    serial_number::SerialNumber,
+    time::Validity,
+};
+
+use crate::idevice_config::with_connect_timeout;
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::{read_plist, send_plist};
+
+/// the pem encoded certificates and key that go into a pairing record
+struct PairCertificates {
+    root_certificate: Vec<u8>,
+    device_certificate: Vec<u8>,
+    private_key: Vec<u8>,
+}
+
+fn make_certificate(
+    signing_key: &RsaPrivateKey,
+    public_key: RsaPublicKey,
+    common_name: Option<&str>,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let name = match common_name {
+        Some(name) => Name::from_str(&format!("CN={name}"))?,
+        None => Name::default(),
+    };
+
+    // lockdownd keeps using the record for as long as the certificates are valid
+    let validity = Validity::from_now(Duration::from_secs(10 * 365 * 24 * 60 * 60))?;
+    let signer = SigningKey::<Sha256>::new(signing_key.clone());
+
+    let certificate = CertificateBuilder::new(
+        Profile::Root,
+        SerialNumber::new(&[1])?,
+        validity,
+        name,
+        SubjectPublicKeyInfo::from_key(public_key)?,
+        &signer,
+    )?
+    .build()?;
+
+    Ok(certificate.to_pem(LineEnding::LF)?.into_bytes())
+}
+
+/// a new root key and certificate for this computer, and a certificate for the device's public
+/// key signed with it. idevice only does this inside `LockdownClient::pair`
+fn make_pair_certificates(device_public_key: &[u8]) -> Result<PairCertificates, Box<dyn Error>> {
+    let device_public_key = RsaPublicKey::from_pkcs1_pem(std::str::from_utf8(device_public_key)?)?;
+    let private_key = RsaPrivateKey::new(&mut rsa::rand_core::OsRng, 2048)?;
+
+    Ok(PairCertificates {
+        root_certificate: make_certificate(&private_key, RsaPublicKey::from(&private_key), None)?,
+        device_certificate: make_certificate(&private_key, device_public_key, Some("Device"))?,
+        private_key: private_key
+            .to_pkcs8_pem(LineEnding::LF)?
+            .as_bytes()
+            .to_vec(),
+    })
+}
+
+/// asks the device to pair, which shows the Trust dialog, until the user answers or
+/// `wait_timeout` runs out. `on_waiting` is called once the device says the dialog is up, or
+/// that it has to be unlocked first. the new pairing record is saved to usbmuxd so every later
+/// connection uses it
+pub async fn pair(
+    provider: &DeviceProvider,
+    wait_timeout: Duration,
+    mut on_waiting: impl FnMut(),
+) -> Result<(), String> {
+    let muxd_provider = provider
+        .usbmuxd()
+        .ok_or("only devices reached through usbmuxd can be paired")?;
+
+    // the record is saved to the usbmuxd the device sits behind, which isn't always the local one
+    let mut usbmuxd = with_connect_timeout(muxd_provider.addr.connect(muxd_provider.tag))
+        .await
+        .map_err(|e| format!("Unable to connect to usbmxud: {e:?}"))?;
+    let buid = usbmuxd
+        .get_buid()
+        .await
+        .map_err(|e| format!("failed to get the usbmuxd buid: {e:?}"))?;
+    let host_id = uuid::Uuid::new_v4().to_string().to_uppercase();
+
+    let mut lockdownd_client = LockdownClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to lockdownd service: {e:?}"))?;
+
+    let device_public_key = lockdownd_client
+        .get_value(Some("DevicePublicKey"), None)
+        .await
+        .map_err(|e| format!("failed to read the device public key: {e:?}"))?
+        .into_data()
+        .ok_or("the device did not send its public key")?;
+    let wifi_address = lockdownd_client
+        .get_value(Some("WiFiAddress"), None)
+        .await
+        .map_err(|e| format!("failed to read the Wi-Fi address: {e:?}"))?
+        .into_string()
+        .ok_or("the device did not send its Wi-Fi address")?;
+
+    // generating the rsa key takes a moment
+    let key = device_public_key.clone();
+    let certificates = tokio::task::spawn_blocking(move || {
+        make_pair_certificates(&key).map_err(|e| format!("failed to make the certificates: {e}"))
+    })
+    .await
+    .map_err(|e| format!("making the certificates panicked: {e:?}"))??;
+
+    let mut pair_record = Dictionary::new();
+    pair_record.insert("DevicePublicKey".into(), Value::Data(device_public_key));
+    pair_record.insert(
+        "DeviceCertificate".into(),
+        Value::Data(certificates.device_certificate),
+    );
+    pair_record.insert(
+        "HostCertificate".into(),
+        Value::Data(certificates.root_certificate.clone()),
+    );
+    pair_record.insert("HostID".into(), Value::String(host_id));
+    pair_record.insert(
+        "RootCertificate".into(),
+        Value::Data(certificates.root_certificate),
+    );
+    pair_record.insert(
+        "RootPrivateKey".into(),
+        Value::Data(certificates.private_key.clone()),
+    );
+    pair_record.insert("WiFiMACAddress".into(), Value::String(wifi_address));
+    pair_record.insert("SystemBUID".into(), Value::String(buid));
+
+    let mut options = Dictionary::new();
+    options.insert("ExtendedPairingErrors".into(), Value::Boolean(true));
+
+    let mut req = Dictionary::new();
+    req.insert("Label".into(), Value::String(provider.label().into()));
+    req.insert("Request".into(), Value::String("Pair".into()));
+    req.insert("PairRecord".into(), Value::Dictionary(pair_record.clone()));
+    req.insert("ProtocolVersion".into(), Value::String("2".into()));
+    req.insert("PairingOptions".into(), Value::Dictionary(options));
+    let req = Value::Dictionary(req);
+
+    let mut waiting = false;
+    let res = timeout(wait_timeout, async {
+        loop {
+            send_plist(&mut lockdownd_client.idevice, req.clone())
+                .await
+                .map_err(|e| format!("failed to send the pair request: {e:?}"))?;
+
+            let res = read_plist(&mut lockdownd_client.idevice)
+                .await
+                .map_err(|e| format!("failed to read the pair response: {e:?}"))?;
+
+            match res.get("Error").and_then(Value::as_string) {
+                None => return Ok(res),
+                // the dialog is up, or only shows up once the device is unlocked
+                Some("PairingDialogResponsePending" | "PasswordProtected") => {
+                    if !waiting {
+                        waiting = true;
+                        on_waiting();
+                    }
+                    sleep(Duration::from_secs(1)).await;
+                }
+                Some("UserDeniedPairing") => {
+                    return Err("the user did not trust this computer".to_string());
+                }
+                Some(e) => return Err(format!("failed to pair: {e}")),
+            }
+        }
+    })
+    .await
+    .map_err(|_| "timed out waiting for the user to trust this computer".to_string())??;
+
+    pair_record.insert(
+        "HostPrivateKey".into(),
+        Value::Data(certificates.private_key),
+    );
+    if let Some(escrow_bag) = res.get("EscrowBag").and_then(Value::as_data) {
+        pair_record.insert("EscrowBag".into(), Value::Data(escrow_bag.to_vec()));
+    }
+
+    let pair_record = PairingFile::from_value(&Value::Dictionary(pair_record))
+        .and_then(PairingFile::serialize)
+        .map_err(|e| format!("the device sent an incomplete pairing record: {e:?}"))?;
+
+    usbmuxd
+        .save_pair_record(&muxd_provider.udid, pair_record)
+        .await
+        .map_err(|e| format!("failed to save the pairing record: {e:?}"))
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PairingValidation {
+    pub valid: bool,
+    /// why the pairing isn't valid
+    pub reason: Option<String>,
+}
+
+/// checks the saved pairing record by starting a lockdownd session with it
+pub async fn validate_pairing(provider: &DeviceProvider) -> Result<PairingValidation, String> {
+    let invalid = |reason: String| PairingValidation {
+        valid: false,
+        reason: Some(reason),
+    };
+
+    let pairing_file = match provider.get_pairing_file().await {
+        Ok(pairing_file) => pairing_file,
+        Err(e) => return Ok(invalid(format!("there is no pairing record: {e:?}"))),
+    };
+
+    let mut lockdownd_client = LockdownClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to lockdownd service: {e:?}"))?;
+
+    Ok(match lockdownd_client.start_session(&pairing_file).await {
+        Ok(_) => PairingValidation {
+            valid: true,
+            reason: None,
+        },
+        Err(e) => invalid(format!("the device refused the pairing record: {e:?}")),
+    })
+}
+
+/// usbmuxd has no `DeletePairRecord` helper in idevice, this sends the message the way
+/// `UsbmuxdConnection` frames its own: a little endian header followed by the xml plist
+async fn delete_pair_record(addr: &UsbmuxdAddr, udid: &str) -> Result<(), String> {
+    let mut socket = with_connect_timeout(addr.to_socket())
+        .await
+        .map_err(|e| format!("Unable to connect to usbmxud: {e:?}"))?;
+
+    let mut req = Dictionary::new();
+    req.insert(
+        "MessageType".into(),
+        Value::String("DeletePairRecord".into()),
+    );
+    req.insert("PairRecordID".into(), Value::String(udid.into()));
+
+    let mut body = Vec::new();
+    Value::Dictionary(req)
+        .to_writer_xml(&mut body)
+        .map_err(|e| format!("failed to encode the usbmuxd request: {e:?}"))?;
+
+    let mut packet = Vec::with_capacity(body.len() + 16);
+    packet.extend_from_slice(&(body.len() as u32 + 16).to_le_bytes());
+    packet.extend_from_slice(&UsbmuxdConnection::XML_PLIST_VERSION.to_le_bytes());
+    packet.extend_from_slice(&UsbmuxdConnection::PLIST_MESSAGE_TYPE.to_le_bytes());
+    packet.extend_from_slice(&0u32.to_le_bytes());
+    packet.extend_from_slice(&body);
+
+    let mut header = [0u8; 16];
+    let res = async {
+        socket.write_all(&packet).await?;
+        socket.read_exact(&mut header).await?;
+
+        let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let mut body = vec![0u8; len.saturating_sub(16) as usize];
+        socket.read_exact(&mut body).await?;
+        Ok::<_, std::io::Error>(body)
+    }
+    .await
+    .map_err(|e| format!("failed to ask usbmuxd to delete the pairing record: {e:?}"))?;
+
+    let res: Dictionary =
+        plist::from_bytes(&res).map_err(|e| format!("usbmuxd sent a bad response: {e:?}"))?;
+
+    match res.get("Number").and_then(Value::as_unsigned_integer) {
+        Some(0) => Ok(()),
+        n => Err(format!(
+            "usbmuxd refused to delete the pairing record: {n:?}"
+        )),
+    }
+}
+
+/// makes the device forget this computer, it asks to trust it again on the next pair. a
+/// record usbmuxd keeps for the device is deleted too, an imported one is left to the host
+pub async fn unpair(provider: &DeviceProvider) -> Result<(), String> {
+    let pairing_file: PairingFile = provider
+        .get_pairing_file()
+        .await
+        .map_err(|e| format!("there is no pairing record to remove: {e:?}"))?;
+
+    let mut lockdownd_client = LockdownClient::connect(provider)
+        .await
+        .map_err(|e| format!("failed to connect to lockdownd service: {e:?}"))?;
+
+    let mut pair_record = Dictionary::new();
+    pair_record.insert("HostID".into(), Value::String(pairing_file.host_id.clone()));
+
+    let mut req = Dictionary::new();
+    req.insert("Label".into(), Value::String(provider.label().into()));
+    req.insert("Request".into(), Value::String("Unpair".into()));
+    req.insert("PairRecord".into(), Value::Dictionary(pair_record));
+
+    send_plist(&mut lockdownd_client.idevice, Value::Dictionary(req))
+        .await
+        .map_err(|e| format!("failed to send the unpair request: {e:?}"))?;
+
+    let res = read_plist(&mut lockdownd_client.idevice)
+        .await
+        .map_err(|e| format!("failed to read the unpair response: {e:?}"))?;
+
+    if let Some(e) = res.get("Error").and_then(Value::as_string) {
+        return Err(format!("the device refused to unpair: {e}"));
+    }
+
+    match provider {
+        DeviceProvider::Usbmuxd(usbmuxd) => delete_pair_record(&usbmuxd.addr, &usbmuxd.udid).await,
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,233 @@
+use std::{future::Future, net::IpAddr, path::Path, pin::Pin};
+
+use idevice::{
+    Idevice, IdeviceError,
+    pairing_file::PairingFile,
+    provider::{IdeviceProvider, TcpProvider, UsbmuxdProvider},
+    usbmuxd::UsbmuxdAddr,
+};
+
+use crate::idevice_config::with_connect_timeout;
+
+/// the provider every export takes
+#[derive(Debug)]
+pub enum DeviceProvider {
+    /// a device usbmuxd knows about, paired with the record usbmuxd keeps for it
+    Usbmuxd(UsbmuxdProvider),
+    /// a device behind a usbmuxd socket, often on another machine, paired with a record the
+    /// host brought along instead of the one usbmuxd keeps
+    Imported {
+        usbmuxd: UsbmuxdProvider,
+        pairing_file: Box<PairingFile>,
+    },
+    /// a device on the network that usbmuxd doesn't see, reached directly by its ip
+    Tcp(Box<TcpProvider>),
+}
+
+impl DeviceProvider {
+    /// the usbmuxd the device is reached through, `None` for direct connections
+    pub fn usbmuxd(&self) -> Option<&UsbmuxdProvider> {
+        match self {
+            Self::Usbmuxd(usbmuxd) | Self::Imported { usbmuxd, .. } => Some(usbmuxd),
+            Self::Tcp(_) => None,
+        }
+    }
+
+    /// a provider for the same device after it reconnected, usbmuxd gives it a new device id
+    /// every time it shows up
+    pub async fn find_again(&self) -> Result<Self, String> {
+        Ok(match self {
+            Self::Usbmuxd(usbmuxd) => Self::Usbmuxd(find_usbmuxd_device(usbmuxd).await?),
+            Self::Imported {
+                usbmuxd,
+                pairing_file,
+            } => Self::Imported {
+                usbmuxd: find_usbmuxd_device(usbmuxd).await?,
+                pairing_file: pairing_file.clone(),
+            },
+            Self::Tcp(tcp) => Self::Tcp(Box::new(TcpProvider {
+                addr: tcp.addr,
+                pairing_file: tcp.pairing_file.clone(),
+                label: tcp.label.clone(),
+            })),
+        })
+    }
+}
+
+async fn find_usbmuxd_device(usbmuxd: &UsbmuxdProvider) -> Result<UsbmuxdProvider, String> {
+    let devices = with_connect_timeout(usbmuxd.addr.connect(0))
+        .await
+        .map_err(|e| format!("Unable to connect to usbmxud: {e:?}"))?
+        .get_devices()
+        .await
+        .map_err(|e| format!("Unable to get devices from usbmuxd: {e:?}"))?;
+
+    devices
+        .iter()
+        .find(|d| d.udid == usbmuxd.udid)
+        .map(|d| d.to_provider(usbmuxd.addr.clone(), &usbmuxd.label))
+        .ok_or(format!("the device {} is not connected", usbmuxd.udid))
+}
+
+impl IdeviceProvider for DeviceProvider {
+    fn connect(
+        &self,
+        port: u16,
+    ) -> Pin<Box<dyn Future<Output = Result<Idevice, IdeviceError>> + Send>> {
+        let connect = match self {
+            Self::Usbmuxd(usbmuxd) | Self::Imported { usbmuxd, .. } => usbmuxd.connect(port),
+            Self::Tcp(tcp) => tcp.connect(port),
+        };
+
+        Box::pin(with_connect_timeout(connect))
+    }
+
+    fn label(&self) -> &str {
+        match self {
+            Self::Usbmuxd(usbmuxd) | Self::Imported { usbmuxd, .. } => usbmuxd.label(),
+            Self::Tcp(tcp) => tcp.label(),
+        }
+    }
+
+    fn get_pairing_file(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = Result<PairingFile, IdeviceError>> + Send>> {
+        match self {
+            Self::Usbmuxd(usbmuxd) => usbmuxd.get_pairing_file(),
+            Self::Tcp(tcp) => tcp.get_pairing_file(),
+            Self::Imported { pairing_file, .. } => {
+                let pairing_file = PairingFile::clone(pairing_file);
+                Box::pin(async move { Ok(pairing_file) })
+            }
+        }
+    }
+}
+
+/// `host:port` is a tcp usbmuxd, anything else a unix socket path
+pub fn parse_usbmuxd_addr(addr: &str) -> Result<UsbmuxdAddr, String> {
+    let tcp = || {
+        addr.parse()
+            .map(UsbmuxdAddr::TcpSocket)
+            .map_err(|e| format!("{addr} is not a valid usbmuxd address: {e:?}"))
+    };
+
+    #[cfg(unix)]
+    if !addr.contains(':') {
+        return Ok(UsbmuxdAddr::UnixSocket(addr.to_string()));
+    }
+
+    tcp()
+}
+
+/// the device the pairing record belongs to, found behind the usbmuxd at `socket_address`.
+/// a record without a udid picks the first device
+pub async fn provider_from_pairing_file(
+    path: impl AsRef<Path>,
+    socket_address: &str,
+    label: &str,
+) -> Result<DeviceProvider, String> {
+    let path = path.as_ref();
+    let pairing_file = PairingFile::read_from_file(path)
+        .map_err(|e| format!("failed to read the pairing file {}: {e:?}", path.display()))?;
+
+    let addr = parse_usbmuxd_addr(socket_address)?;
+    let devices = with_connect_timeout(addr.connect(0))
+        .await
+        .map_err(|e| format!("Unable to connect to usbmxud at {socket_address}: {e:?}"))?
+        .get_devices()
+        .await
+        .map_err(|e| format!("Unable to get devices from usbmuxd: {e:?}"))?;
+
+    let device = match &pairing_file.udid {
+        Some(udid) => devices.iter().find(|d| d.udid == *udid).ok_or(format!(
+            "the device {udid} is not connected to {socket_address}"
+        ))?,
+        None => devices
+            .first()
+            .ok_or(format!("no devices are connected to {socket_address}"))?,
+    };
+
+    Ok(DeviceProvider::Imported {
+        usbmuxd: device.to_provider(addr, label),
+        pairing_file: Box::new(pairing_file),
+    })
+}
+
+/// connects straight to the device at `ip`, which has to have Wi-Fi connections enabled
+pub fn provider_from_ip(
+    ip: &str,
+    pairing_file_path: impl AsRef<Path>,
+    label: &str,
+) -> Result<DeviceProvider, String> {
+    let addr: IpAddr = ip
+        .parse()
+        .map_err(|e| format!("{ip} is not a valid ip address: {e:?}"))?;
+
+    let path = pairing_file_path.as_ref();
+    let pairing_file = PairingFile::read_from_file(path)
+        .map_err(|e| format!("failed to read the pairing file {}: {e:?}", path.display()))?;
+
+    Ok(DeviceProvider::Tcp(Box::new(TcpProvider {
+        addr,
+        pairing_file,
+        label: label.to_string(),
+    })))
+}
+
+/// writes the device's pairing record as an xml plist that `provider_from_pairing_file` reads
+pub async fn export_pairing_file(
+    provider: &DeviceProvider,
+    path: impl AsRef<Path>,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let pairing_file = provider
+        .get_pairing_file()
+        .await
+        .map_err(|e| format!("Failed to get the pairing file: {e:?}"))?
+        .serialize()
+        .map_err(|e| format!("failed to serialize the pairing record: {e:?}"))?;
+
+    tokio::fs::write(path, pairing_file)
+        .await
+        .map_err(|e| format!("failed to write {}: {e:?}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_usbmuxd_addr_reads_tcp_addresses() {
+        match parse_usbmuxd_addr("127.0.0.1:27015") {
+            Ok(UsbmuxdAddr::TcpSocket(addr)) => assert_eq!(addr.to_string(), "127.0.0.1:27015"),
+            other => panic!("expected a tcp address, got {other:?}"),
+        }
+
+        match parse_usbmuxd_addr("[::1]:27015") {
+            Ok(UsbmuxdAddr::TcpSocket(addr)) => assert_eq!(addr.port(), 27015),
+            other => panic!("expected a tcp address, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_usbmuxd_addr_rejects_malformed_tcp_addresses() {
+        assert!(parse_usbmuxd_addr("localhost:27015").is_err());
+        assert!(parse_usbmuxd_addr("127.0.0.1:").is_err());
+        assert!(parse_usbmuxd_addr("127.0.0.1:99999").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parse_usbmuxd_addr_reads_anything_else_as_a_unix_socket() {
+        match parse_usbmuxd_addr("/var/run/usbmuxd") {
+            Ok(UsbmuxdAddr::UnixSocket(path)) => assert_eq!(path, "/var/run/usbmuxd"),
+            other => panic!("expected a unix socket, got {other:?}"),
+        }
+    }
+
+    #[cfg(not(unix))]
+    #[test]
+    fn parse_usbmuxd_addr_needs_a_port_without_unix_sockets() {
+        assert!(parse_usbmuxd_addr("127.0.0.1").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+use serde::Serialize;
+
+/// `RegionInfo` codes and the countries the devices were sold in
+#[rustfmt::skip]
+static REGIONS: &[(&str, &str)] = &[
+    ("AB/A", "Middle East (Saudi Arabia, UAE, Qatar, Jordan, Egypt)"),
+    ("AE/A", "United Arab Emirates"),
+    ("AH/A", "Bahrain, Kuwait"),
+    ("B/A", "United Kingdom, Ireland"),
+    ("BZ/A", "Brazil"),
+    ("C/A", "Canada"),
+    ("CH/A", "China"),
+    ("CL/A", "Canada"),
+    ("CZ/A", "Czech Republic"),
+    ("D/A", "Germany"),
+    ("DN/A", "Austria, Germany, Netherlands"),
+    ("E/A", "Mexico"),
+    ("EE/A", "Estonia"),
+    ("F/A", "France"),
+    ("FB/A", "France, Luxembourg"),
+    ("FD/A", "Austria, Liechtenstein, Switzerland"),
+    ("FS/A", "Finland"),
+    ("GR/A", "Greece"),
+    ("HB/A", "Israel"),
+    ("HN/A", "India"),
+    ("ID/A", "Indonesia"),
+    ("IP/A", "Italy"),
+    ("J/A", "Japan"),
+    ("KH/A", "South Korea"),
+    ("KN/A", "Denmark, Norway"),
+    ("KS/A", "Finland, Sweden"),
+    ("LA/A", "Latin America (Colombia, Ecuador, El Salvador, Guatemala, Honduras, Peru)"),
+    ("LE/A", "Argentina"),
+    ("LL/A", "United States"),
+    ("LZ/A", "Chile, Paraguay, Uruguay"),
+    ("MG/A", "Hungary"),
+    ("MY/A", "Malaysia"),
+    ("N/A", "Netherlands"),
+    ("NF/A", "Belgium, France, Luxembourg"),
+    ("PL/A", "Poland"),
+    ("PO/A", "Portugal"),
+    ("PP/A", "Philippines"),
+    ("QL/A", "Italy, Portugal, Spain"),
+    ("QN/A", "Denmark, Iceland, Norway, Sweden"),
+    ("RK/A", "Kazakhstan"),
+    ("RM/A", "Russia, Kazakhstan"),
+    ("RO/A", "Romania"),
+    ("RP/A", "Russia"),
+    ("RR/A", "Russia, Moldova"),
+    ("RS/A", "Russia"),
+    ("RU/A", "Russia"),
+    ("SL/A", "Slovakia"),
+    ("SO/A", "South Africa"),
+    ("T/A", "Italy"),
+    ("TA/A", "Taiwan"),
+    ("TH/A", "Thailand"),
+    ("TU/A", "Turkey"),
+    ("TY/A", "Italy"),
+    ("UA/A", "Ukraine"),
+    ("VN/A", "Vietnam"),
+    ("X/A", "Australia, New Zealand"),
+    ("Y/A", "Spain"),
+    ("ZA/A", "Singapore"),
+    ("ZD/A", "Europe (Austria, Belgium, France, Germany, Luxembourg, Monaco, Netherlands, Switzerland)"),
+    ("ZG/A", "Denmark"),
+    ("ZO/A", "United Kingdom"),
+    ("ZP/A", "Hong Kong, Macau"),
+    ("ZQ/A", "Jamaica"),
+];
+
+/// the countries a `RegionInfo` code like `LL/A` was sold in
+pub fn region_name(code: &str) -> Option<&'static str> {
+    let code = code.trim();
+
+    REGIONS
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code))
+        .map(|(_, name)| *name)
+}
+
+/// how the device was sold, told by the first letter of its model number
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PurchaseType {
+    New,
+    Refurbished,
+    Replacement,
+    Personalized,
+    Demo,
+    #[default]
+    Unknown,
+}
+
+impl PurchaseType {
+    pub fn from_model_number(model_number: &str) -> Self {
+        let Some(letter) = model_number.trim().chars().next() else {
+            return Self::Unknown;
+        };
+
+        PURCHASE_TYPES
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(&letter))
+            .map_or(Self::Unknown, |(_, purchase_type)| *purchase_type)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::New => "New Device",
+            Self::Refurbished => "Refurbished Device",
+            Self::Replacement => "Warranty Replacement Device",
+            Self::Personalized => "Personalized Device",
+            Self::Demo => "Demo Device",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+/// the first letter of a model number and how a device with it was sold
+static PURCHASE_TYPES: &[(char, PurchaseType)] = &[
+    ('M', PurchaseType::New),
+    ('F', PurchaseType::Refurbished),
+    ('N', PurchaseType::Replacement),
+    ('P', PurchaseType::Personalized),
+    ('3', PurchaseType::Demo),
+];
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ModelNumberInfo {
+    /// `ModelNumber` as lockdownd reports it, like `MLPF2`
+    pub model_number: String,
+    pub purchase_type: PurchaseType,
+    pub purchase_type_description: &'static str,
+    /// `RegionInfo` as lockdownd reports it, like `LL/A`
+    pub region_code: String,
+    pub region_name: Option<&'static str>,
+}
+
+/// splits what a model number and region code say, with no device involved
+pub fn decode_model_number(model_number: &str, region_code: &str) -> ModelNumberInfo {
+    let purchase_type = PurchaseType::from_model_number(model_number);
+
+    ModelNumberInfo {
+        model_number: model_number.trim().to_string(),
+        purchase_type,
+        purchase_type_description: purchase_type.description(),
+        region_code: region_code.trim().to_string(),
+        region_name: region_name(region_code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn region_name_knows_common_codes() {
+        assert_eq!(region_name("LL/A"), Some("United States"));
+        assert_eq!(region_name("ZA/A"), Some("Singapore"));
+        assert_eq!(
+            region_name("AB/A"),
+            Some("Middle East (Saudi Arabia, UAE, Qatar, Jordan, Egypt)")
+        );
+        assert_eq!(region_name("TA/A"), Some("Taiwan"));
+    }
+
+    #[test]
+    fn region_name_ignores_case_and_whitespace() {
+        assert_eq!(region_name(" ll/a\n"), Some("United States"));
+    }
+
+    #[test]
+    fn region_name_is_none_for_unknown_codes() {
+        assert_eq!(region_name("QQ/A"), None);
+        assert_eq!(region_name("LL"), None);
+        assert_eq!(region_name(""), None);
+    }
+
+    #[test]
+    fn purchase_type_comes_from_the_first_letter() {
+        assert_eq!(PurchaseType::from_model_number("MLPF2"), PurchaseType::New);
+        assert_eq!(
+            PurchaseType::from_model_number("FLPF2"),
+            PurchaseType::Refurbished
+        );
+        assert_eq!(
+            PurchaseType::from_model_number("NLPF2"),
+            PurchaseType::Replacement
+        );
+        assert_eq!(
+            PurchaseType::from_model_number("PLPF2"),
+            PurchaseType::Personalized
+        );
+        assert_eq!(PurchaseType::from_model_number("3D123"), PurchaseType::Demo);
+        assert_eq!(PurchaseType::from_model_number(" mlpf2"), PurchaseType::New);
+    }
+
+    #[test]
+    fn purchase_type_is_unknown_for_other_letters() {
+        assert_eq!(
+            PurchaseType::from_model_number("ZLPF2"),
+            PurchaseType::Unknown
+        );
+        assert_eq!(PurchaseType::from_model_number(""), PurchaseType::Unknown);
+        assert_eq!(
+            PurchaseType::from_model_number("   "),
+            PurchaseType::Unknown
+        );
+    }
+
+    #[test]
+    fn decode_model_number_splits_both_codes() {
+        let info = decode_model_number(" FLPF2 ", "ZA/A");
+
+        assert_eq!(info.model_number, "FLPF2");
+        assert_eq!(info.purchase_type, PurchaseType::Refurbished);
+        assert_eq!(info.purchase_type_description, "Refurbished Device");
+        assert_eq!(info.region_code, "ZA/A");
+        assert_eq!(info.region_name, Some("Singapore"));
+    }
+
+    #[test]
+    fn decode_model_number_keeps_unknown_codes() {
+        let info = decode_model_number("", "XX/X");
+
+        assert_eq!(info.purchase_type, PurchaseType::Unknown);
+        assert_eq!(info.purchase_type_description, "Unknown");
+        assert_eq!(info.region_code, "XX/X");
+        assert_eq!(info.region_name, None);
+    }
+}
@@ -0,0 +1,36 @@
+use idevice::{IdeviceError, IdeviceService, screenshotr::ScreenshotService};
+
+use crate::idevice_provider::DeviceProvider;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// the screen as a png. screenshotr only runs while the developer disk image is mounted
+pub async fn take_screenshot(provider: &DeviceProvider) -> Result<Vec<u8>, String> {
+    let mut screenshotr = ScreenshotService::connect(provider)
+        .await
+        .map_err(|e| match e {
+            // lockdownd doesn't know the service until the image is mounted
+            IdeviceError::ImageNotMounted => ddi_missing(),
+            IdeviceError::UnknownErrorType(e) if e == "InvalidService" => ddi_missing(),
+            IdeviceError::DeveloperModeNotEnabled => {
+                "developer mode has to be enabled to take screenshots".into()
+            }
+            e => format!("failed to connect to the screenshot service: {e:?}"),
+        })?;
+
+    let screenshot = screenshotr
+        .take_screenshot()
+        .await
+        .map_err(|e| format!("failed to take a screenshot: {e:?}"))?;
+
+    // very old iOS versions send tiffs
+    if !screenshot.starts_with(PNG_SIGNATURE) {
+        return Err("the device sent the screenshot in a format other than png".into());
+    }
+
+    Ok(screenshot)
+}
+
+fn ddi_missing() -> String {
+    "the screenshot service is not available, mount the developer disk image first".into()
+}
@@ -0,0 +1,170 @@
+use idevice::IdeviceError;
+use plist::Value;
+
+use crate::idevice_helper::get_lockdownd_client;
+use crate::idevice_provider::DeviceProvider;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Bool,
+}
+
+/// a lockdownd value the library is willing to change
+#[derive(Debug, Clone, Copy)]
+pub struct WritableValue {
+    pub domain: Option<&'static str>,
+    pub key: &'static str,
+    pub kind: ValueKind,
+}
+
+const fn writable(
+    domain: Option<&'static str>,
+    key: &'static str,
+    kind: ValueKind,
+) -> WritableValue {
+    WritableValue { domain, key, kind }
+}
+
+const WIRELESS_LOCKDOWN: &str = "com.apple.mobile.wireless_lockdown";
+
+/// everything else is refused before it reaches the device
+#[rustfmt::skip]
+pub static WRITABLE_VALUES: &[WritableValue] = &[
+    writable(None, "DeviceName", ValueKind::String),
+    writable(Some("com.apple.international"), "Language", ValueKind::String),
+    writable(Some("com.apple.international"), "Locale", ValueKind::String),
+    writable(Some(WIRELESS_LOCKDOWN), "EnableWifiConnections", ValueKind::Bool),
+];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetValueStatus {
+    Ok = 0,
+    /// the key isn't in `WRITABLE_VALUES`
+    NotAllowed,
+    /// the value doesn't have the type the key takes
+    WrongType,
+    /// lockdownd refused the change
+    Rejected,
+    /// couldn't reach lockdownd at all
+    NotConnected,
+    /// an argument was null, not utf-8 or empty
+    InvalidArgument,
+}
+
+#[derive(Debug, Clone)]
+pub struct SetValueError {
+    pub status: SetValueStatus,
+    pub message: String,
+}
+
+impl SetValueError {
+    pub fn new(status: SetValueStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn find_writable(domain: Option<&str>, key: &str) -> Option<&'static WritableValue> {
+    WRITABLE_VALUES
+        .iter()
+        .find(|w| w.domain == domain && w.key == key)
+}
+
+fn json_to_value(kind: ValueKind, value: &serde_json::Value) -> Option<Value> {
+    match (kind, value) {
+        (ValueKind::String, serde_json::Value::String(s)) => Some(Value::String(s.clone())),
+        (ValueKind::Bool, serde_json::Value::Bool(b)) => Some(Value::Boolean(*b)),
+        _ => None,
+    }
+}
+
+/// sets a whitelisted lockdownd value, `value` has to match the type the key takes
+pub async fn set_device_value(
+    provider: &DeviceProvider,
+    domain: Option<&str>,
+    key: &str,
+    value: &serde_json::Value,
+) -> Result<(), SetValueError> {
+    let writable = find_writable(domain, key).ok_or_else(|| {
+        SetValueError::new(
+            SetValueStatus::NotAllowed,
+            format!(
+                "{key} in {} is not writable",
+                domain.unwrap_or("the default domain")
+            ),
+        )
+    })?;
+
+    let value = json_to_value(writable.kind, value).ok_or_else(|| {
+        SetValueError::new(
+            SetValueStatus::WrongType,
+            format!("{key} takes a {:?}, got {value}", writable.kind),
+        )
+    })?;
+
+    let mut lockdownd_client = get_lockdownd_client(provider)
+        .await
+        .map_err(|e| SetValueError::new(SetValueStatus::NotConnected, e))?;
+
+    lockdownd_client
+        .set_value(key, value, domain)
+        .await
+        .map_err(|e| {
+            SetValueError::new(
+                SetValueStatus::Rejected,
+                format!("the device rejected {key}: {e:?}"),
+            )
+        })
+}
+
+pub async fn set_device_name(provider: &DeviceProvider, name: &str) -> Result<(), SetValueError> {
+    if name.trim().is_empty() {
+        return Err(SetValueError::new(
+            SetValueStatus::InvalidArgument,
+            "the device name can't be empty",
+        ));
+    }
+
+    set_device_value(
+        provider,
+        None,
+        "DeviceName",
+        &serde_json::Value::String(name.to_string()),
+    )
+    .await
+}
+
+/// whether the device accepts connections over Wi-Fi, devices that were never switched report
+/// nothing which counts as off
+pub async fn get_wifi_connections(provider: &DeviceProvider) -> Result<bool, String> {
+    let mut lockdownd_client = get_lockdownd_client(provider).await?;
+
+    match lockdownd_client
+        .get_value(Some("EnableWifiConnections"), Some(WIRELESS_LOCKDOWN))
+        .await
+    {
+        Ok(value) => value
+            .as_boolean()
+            .ok_or_else(|| format!("EnableWifiConnections isn't a bool: {value:?}")),
+        // lockdownd leaves `Value` out of the reply when the key was never set
+        Err(IdeviceError::UnexpectedResponse) => Ok(false),
+        Err(e) => Err(format!("failed to read EnableWifiConnections: {e:?}")),
+    }
+}
+
+pub async fn set_wifi_connections(
+    provider: &DeviceProvider,
+    enabled: bool,
+) -> Result<(), SetValueError> {
+    set_device_value(
+        provider,
+        Some(WIRELESS_LOCKDOWN),
+        "EnableWifiConnections",
+        &serde_json::Value::Bool(enabled),
+    )
+    .await
+}
@@ -0,0 +1,272 @@
+use std::{collections::HashSet, path::Path};
+
+use plist::{Dictionary, Value};
+use serde::Serialize;
+
+use crate::idevice_apps::{AppFilter, list_apps};
+use crate::idevice_helper::get_springboard_client;
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_utils::{
+    lossless_json_to_plist, plist_to_lossless_json, read_plist, read_plist_value, send_plist,
+};
+
+pub async fn get_app_icon(provider: &DeviceProvider, bundle_id: &str) -> Result<Vec<u8>, String> {
+    let mut springboard = get_springboard_client(provider).await?;
+
+    springboard
+        .get_icon_pngdata(bundle_id.to_string())
+        .await
+        .map_err(|e| format!("failed to get the icon of {bundle_id}: {e:?}"))
+}
+
+pub async fn get_wallpaper(provider: &DeviceProvider) -> Result<Vec<u8>, String> {
+    let mut springboard = get_springboard_client(provider).await?;
+
+    let mut req = Dictionary::new();
+    req.insert(
+        "command".into(),
+        Value::String("getHomeScreenWallpaperPNGData".into()),
+    );
+
+    send_plist(&mut springboard.idevice, Value::Dictionary(req))
+        .await
+        .map_err(|e| format!("failed to ask for the wallpaper: {e:?}"))?;
+
+    let mut res = read_plist(&mut springboard.idevice)
+        .await
+        .map_err(|e| format!("failed to read the wallpaper: {e:?}"))?;
+
+    match res.remove("pngData") {
+        Some(Value::Data(png)) => Ok(png),
+        _ => Err("the device did not send any wallpaper data".into()),
+    }
+}
+
+/// pages of icons as springboard sees them, the first page is the dock
+pub async fn get_icon_state(provider: &DeviceProvider) -> Result<Value, String> {
+    let mut springboard = get_springboard_client(provider).await?;
+
+    let mut req = Dictionary::new();
+    req.insert("command".into(), Value::String("getIconState".into()));
+    req.insert("formatVersion".into(), Value::String("2".into()));
+
+    send_plist(&mut springboard.idevice, Value::Dictionary(req))
+        .await
+        .map_err(|e| format!("failed to ask for the icon state: {e:?}"))?;
+
+    // springboard answers with an array rather than a dictionary
+    read_plist_value(&mut springboard.idevice)
+        .await
+        .map_err(|e| format!("failed to read the icon state: {e:?}"))
+}
+
+pub async fn set_icon_state(provider: &DeviceProvider, state: Value) -> Result<(), String> {
+    let mut springboard = get_springboard_client(provider).await?;
+
+    let mut req = Dictionary::new();
+    req.insert("command".into(), Value::String("setIconState".into()));
+    req.insert("iconState".into(), state);
+
+    // springboard doesn't answer this one
+    send_plist(&mut springboard.idevice, Value::Dictionary(req))
+        .await
+        .map_err(|e| format!("failed to send the icon state: {e:?}"))
+}
+
+/// every bundle id an icon state refers to, including the ones inside folders
+fn layout_bundle_ids(state: &Value, out: &mut Vec<String>) {
+    match state {
+        Value::Array(items) => items.iter().for_each(|i| layout_bundle_ids(i, out)),
+        Value::Dictionary(icon) => {
+            if let Some(id) = icon.get("bundleIdentifier").and_then(Value::as_string) {
+                out.push(id.to_string());
+            }
+            if let Some(lists) = icon.get("iconLists") {
+                layout_bundle_ids(lists, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// whether an icon survives pruning, folders are dropped once they have no icons left
+fn keeps_icon(item: &Value, missing: &[String]) -> bool {
+    let Some(icon) = item.as_dictionary() else {
+        return true;
+    };
+
+    if let Some(id) = icon.get("bundleIdentifier").and_then(Value::as_string) {
+        return !missing.iter().any(|m| m == id);
+    }
+
+    icon.get("iconLists")
+        .and_then(Value::as_array)
+        .is_none_or(|pages| !pages.is_empty())
+}
+
+/// drops the icons of `missing` apps, and the folder pages and folders that end up empty.
+/// the home screen pages themselves keep their place
+fn prune_layout(state: &mut Value, missing: &[String]) {
+    match state {
+        Value::Array(items) => {
+            items.iter_mut().for_each(|i| prune_layout(i, missing));
+            items.retain(|i| keeps_icon(i, missing));
+        }
+        Value::Dictionary(icon) => {
+            if let Some(Value::Array(pages)) = icon.get_mut("iconLists") {
+                pages.iter_mut().for_each(|p| prune_layout(p, missing));
+                pages.retain(|p| p.as_array().is_none_or(|icons| !icons.is_empty()));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_plist_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("plist"))
+}
+
+/// saves the layout as a plist when `path` ends with `.plist`, otherwise as lossless json
+/// (see `plist_to_lossless_json`) so it reads back the same
+pub async fn export_layout(
+    provider: &DeviceProvider,
+    path: impl AsRef<Path>,
+) -> Result<(), String> {
+    let path = path.as_ref();
+    let state = get_icon_state(provider).await?;
+
+    if is_plist_file(path) {
+        state
+            .to_file_xml(path)
+            .map_err(|e| format!("failed to write {}: {e:?}", path.display()))
+    } else {
+        let json = serde_json::to_vec_pretty(&plist_to_lossless_json(&state))
+            .map_err(|e| format!("failed to serialize the layout: {e:?}"))?;
+
+        tokio::fs::write(path, json)
+            .await
+            .map_err(|e| format!("failed to write {}: {e:?}", path.display()))
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct LayoutImport {
+    pub pages: usize,
+    pub icons: usize,
+    /// apps the layout refers to that aren't installed on this device
+    pub missing: Vec<String>,
+}
+
+/// applies a layout saved by `export_layout`. when some of its apps aren't installed it fails,
+/// unless `skip_missing` is set, then their icons are left out
+pub async fn import_layout(
+    provider: &DeviceProvider,
+    path: impl AsRef<Path>,
+    skip_missing: bool,
+) -> Result<LayoutImport, String> {
+    let path = path.as_ref();
+
+    let mut state: Value = if is_plist_file(path) {
+        Value::from_file(path).map_err(|e| format!("failed to read {}: {e:?}", path.display()))?
+    } else {
+        let json = tokio::fs::read(path)
+            .await
+            .map_err(|e| format!("failed to read {}: {e:?}", path.display()))?;
+
+        serde_json::from_slice(&json)
+            .map_err(|e| e.to_string())
+            .and_then(|json| lossless_json_to_plist(&json))
+            .map_err(|e| format!("{} is not a layout: {e}", path.display()))?
+    };
+
+    if state.as_array().is_none() {
+        return Err(format!(
+            "{} is not a layout, expected a list of pages",
+            path.display()
+        ));
+    }
+
+    let installed = list_apps(provider, AppFilter::default())
+        .await?
+        .into_iter()
+        .map(|app| app.bundle_id)
+        .collect::<HashSet<_>>();
+
+    let mut bundle_ids = Vec::new();
+    layout_bundle_ids(&state, &mut bundle_ids);
+
+    let mut missing = bundle_ids
+        .iter()
+        .filter(|id| !installed.contains(*id))
+        .cloned()
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+
+    if !missing.is_empty() {
+        if !skip_missing {
+            return Err(format!(
+                "the layout refers to apps that are not installed: {}",
+                missing.join(", ")
+            ));
+        }
+
+        prune_layout(&mut state, &missing);
+    }
+
+    let report = LayoutImport {
+        pages: state.as_array().map_or(0, Vec::len),
+        icons: bundle_ids.len() - bundle_ids.iter().filter(|id| missing.contains(id)).count(),
+        missing,
+    };
+
+    set_icon_state(provider, state).await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(id: &str) -> Value {
+        let mut icon = Dictionary::new();
+        icon.insert("bundleIdentifier".into(), Value::String(id.into()));
+        Value::Dictionary(icon)
+    }
+
+    fn folder(pages: Vec<Vec<Value>>) -> Value {
+        let mut icon = Dictionary::new();
+        icon.insert("listType".into(), Value::String("folder".into()));
+        icon.insert(
+            "iconLists".into(),
+            Value::Array(pages.into_iter().map(Value::Array).collect()),
+        );
+        Value::Dictionary(icon)
+    }
+
+    #[test]
+    fn prune_layout_drops_folders_left_empty() {
+        let mut state = Value::Array(vec![
+            Value::Array(vec![app("com.apple.mobilesafari")]),
+            Value::Array(vec![
+                folder(vec![vec![app("com.example.gone")]]),
+                folder(vec![
+                    vec![app("com.example.gone")],
+                    vec![app("com.apple.Maps")],
+                ]),
+            ]),
+            Value::Array(vec![app("com.example.gone")]),
+        ]);
+
+        prune_layout(&mut state, &["com.example.gone".into()]);
+
+        let expected = Value::Array(vec![
+            Value::Array(vec![app("com.apple.mobilesafari")]),
+            Value::Array(vec![folder(vec![vec![app("com.apple.Maps")]])]),
+            Value::Array(vec![]),
+        ]);
+        assert_eq!(state, expected);
+    }
+}
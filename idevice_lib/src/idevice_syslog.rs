@@ -0,0 +1,473 @@
+use std::{
+    ffi::{CString, c_char},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use idevice::syslog_relay::SyslogRelayClient;
+use regex::Regex;
+use serde::Deserialize;
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+    sync::Notify,
+    time::sleep,
+};
+
+use crate::idevice_helper::get_syslog_client;
+use crate::idevice_provider::DeviceProvider;
+
+/// how often a recording looks for its device again after the relay dropped
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum LogLevel {
+    /// lines without a level, like the rest of a multi-line message
+    #[default]
+    Unknown = 0,
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Fault,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl From<&str> for LogLevel {
+    fn from(value: &str) -> Self {
+        match value {
+            "Debug" => Self::Debug,
+            "Info" => Self::Info,
+            "Notice" => Self::Notice,
+            "Warning" => Self::Warning,
+            "Error" => Self::Error,
+            "Fault" => Self::Fault,
+            "Critical" => Self::Critical,
+            "Alert" => Self::Alert,
+            "Emergency" => Self::Emergency,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SyslogLine {
+    /// `Oct 19 01:24:39`, the relay doesn't send the year
+    pub timestamp: String,
+    pub device: String,
+    pub process: String,
+    pub pid: u32,
+    pub level: LogLevel,
+    pub message: String,
+}
+
+impl SyslogLine {
+    /// parses `Oct 19 01:24:39 iPhone SpringBoard(FrontBoard)[57] <Notice>: message`,
+    /// anything else is a continuation of `previous` and keeps its header
+    pub fn parse(line: &str, previous: Option<&SyslogLine>) -> Self {
+        Self::parse_header(line).unwrap_or_else(|| Self {
+            message: line.to_string(),
+            ..previous.cloned().unwrap_or_default()
+        })
+    }
+
+    fn parse_header(line: &str) -> Option<Self> {
+        let timestamp = line.get(..15)?;
+        let rest = line.get(16..)?;
+
+        let (device, rest) = rest.split_once(' ')?;
+        let (process_pid, rest) = rest.split_once(' ')?;
+        let (process, pid) = process_pid.strip_suffix(']')?.rsplit_once('[')?;
+
+        let rest = rest.strip_prefix('<')?;
+        let (level, message) = rest.split_once(">:")?;
+
+        Some(Self {
+            timestamp: timestamp.to_string(),
+            device: device.to_string(),
+            process: process.to_string(),
+            pid: pid.parse().ok()?,
+            level: LogLevel::from(level),
+            message: message.strip_prefix(' ').unwrap_or(message).to_string(),
+        })
+    }
+
+    /// the process name without the `(library)` suffix
+    pub fn process_name(&self) -> &str {
+        self.process
+            .split_once('(')
+            .map_or(self.process.as_str(), |(name, _)| name)
+    }
+}
+
+/// which lines `stream_syslog` passes on, a line has to match all of the fields that are set
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct SyslogFilter {
+    /// only lines from these processes, empty means all of them
+    pub processes: Vec<String>,
+    /// only lines whose message matches
+    pub regex: Option<String>,
+    pub min_level: LogLevel,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledSyslogFilter {
+    processes: Vec<String>,
+    regex: Option<Regex>,
+    min_level: LogLevel,
+}
+
+impl SyslogFilter {
+    pub fn compile(self) -> Result<CompiledSyslogFilter, String> {
+        let regex = self
+            .regex
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|e| format!("bad syslog regex: {e}"))?;
+
+        Ok(CompiledSyslogFilter {
+            processes: self.processes,
+            regex,
+            min_level: self.min_level,
+        })
+    }
+}
+
+impl CompiledSyslogFilter {
+    pub fn matches(&self, line: &SyslogLine) -> bool {
+        line.level >= self.min_level
+            && (self.processes.is_empty()
+                || self
+                    .processes
+                    .iter()
+                    .any(|p| p == line.process_name() || *p == line.process))
+            && self
+                .regex
+                .as_ref()
+                .is_none_or(|r| r.is_match(&line.message))
+    }
+}
+
+/// reads lines until the relay fails, only the ones that match `filter` reach `on_line`
+pub async fn stream_syslog(
+    mut syslog: SyslogRelayClient,
+    filter: CompiledSyslogFilter,
+    mut on_line: impl FnMut(&SyslogLine),
+) -> Result<(), String> {
+    let mut previous: Option<SyslogLine> = None;
+
+    loop {
+        let raw = syslog
+            .next()
+            .await
+            .map_err(|e| format!("the syslog relay stopped: {e:?}"))?;
+
+        for raw in raw.lines().filter(|l| !l.trim().is_empty()) {
+            let line = SyslogLine::parse(raw, previous.as_ref());
+
+            if filter.matches(&line) {
+                on_line(&line);
+            }
+
+            previous = Some(line);
+        }
+    }
+}
+
+/// a parsed line, the strings are only valid during the callback
+#[repr(C)]
+pub struct SyslogLineFFI {
+    pub timestamp: *const c_char,
+    pub device: *const c_char,
+    pub process: *const c_char,
+    pub pid: u32,
+    pub level: LogLevel,
+    pub message: *const c_char,
+}
+
+/// hands `line` to `callback` as a `SyslogLineFFI`
+pub fn with_ffi_line(line: &SyslogLine, callback: impl FnOnce(&SyslogLineFFI)) {
+    let c = |s: &str| CString::new(s.replace('\0', "")).unwrap_or_default();
+    let (timestamp, device, process, message) = (
+        c(&line.timestamp),
+        c(&line.device),
+        c(&line.process),
+        c(&line.message),
+    );
+
+    callback(&SyslogLineFFI {
+        timestamp: timestamp.as_ptr(),
+        device: device.as_ptr(),
+        process: process.as_ptr(),
+        pid: line.pid,
+        level: line.level,
+        message: message.as_ptr(),
+    });
+}
+
+/// how the recorder rotates its file, every field but `path` is optional
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecorderConfig {
+    pub path: PathBuf,
+    /// rotate once the file grows past this many bytes
+    pub max_bytes: Option<u64>,
+    /// rotate once the file is older than this
+    pub max_age_secs: Option<u64>,
+    /// how many rotated files to keep next to `path` as `path.1`, `path.2`, ..
+    pub keep_files: Option<usize>,
+    pub filter: SyslogFilter,
+}
+
+/// writes into `path` and moves it aside as `path.1` when it's too big or too old
+struct RotatingFile {
+    config: RecorderConfig,
+    file: BufWriter<File>,
+    written: u64,
+    opened_at: Instant,
+}
+
+impl RotatingFile {
+    async fn open(config: RecorderConfig) -> Result<Self, String> {
+        let file = Self::open_file(&config.path).await?;
+        let written = file.get_ref().metadata().await.map_or(0, |m| m.len());
+
+        Ok(Self {
+            config,
+            file,
+            written,
+            opened_at: Instant::now(),
+        })
+    }
+
+    async fn open_file(path: &Path) -> Result<BufWriter<File>, String> {
+        File::options()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .map(BufWriter::new)
+            .map_err(|e| format!("failed to open {}: {e:?}", path.display()))
+    }
+
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    fn should_rotate(&self) -> bool {
+        self.config.max_bytes.is_some_and(|max| self.written >= max)
+            || self
+                .config
+                .max_age_secs
+                .is_some_and(|max| self.opened_at.elapsed() >= Duration::from_secs(max))
+    }
+
+    async fn rotate(&mut self) -> Result<(), String> {
+        self.file
+            .flush()
+            .await
+            .map_err(|e| format!("failed to flush the syslog file: {e:?}"))?;
+
+        let keep = self.config.keep_files.unwrap_or(5).max(1);
+        let _ = tokio::fs::remove_file(self.rotated_path(keep)).await;
+        for n in (1..keep).rev() {
+            let _ = tokio::fs::rename(self.rotated_path(n), self.rotated_path(n + 1)).await;
+        }
+        tokio::fs::rename(&self.config.path, self.rotated_path(1))
+            .await
+            .map_err(|e| format!("failed to rotate the syslog file: {e:?}"))?;
+
+        self.file = Self::open_file(&self.config.path).await?;
+        self.written = 0;
+        self.opened_at = Instant::now();
+
+        Ok(())
+    }
+
+    async fn write_line(&mut self, line: &str) -> Result<(), String> {
+        if self.should_rotate() {
+            self.rotate().await?;
+        }
+
+        self.file
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .map_err(|e| format!("failed to write to the syslog file: {e:?}"))?;
+        self.written += line.len() as u64 + 1;
+
+        Ok(())
+    }
+
+    /// marks something that happened to the recording itself, not a device line
+    async fn note(&mut self, note: &str) -> Result<(), String> {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S");
+        self.write_line(&format!("--- {now} {note} ---")).await?;
+        self.flush().await
+    }
+
+    async fn flush(&mut self) -> Result<(), String> {
+        self.file
+            .flush()
+            .await
+            .map_err(|e| format!("failed to flush the syslog file: {e:?}"))
+    }
+}
+
+/// connects to the relay of the recorded device again, the old provider points to a device id
+/// that's gone after a reconnect
+async fn reconnect_syslog(device: &DeviceProvider) -> Result<SyslogRelayClient, String> {
+    let provider = device.find_again().await?;
+    get_syslog_client(&provider).await
+}
+
+/// writes the syslog into a rotating file until `stop` is notified. when the relay drops it
+/// notes the gap in the file and tries to find `device` again every `RECONNECT_INTERVAL`
+pub async fn record_syslog(
+    device: DeviceProvider,
+    syslog: SyslogRelayClient,
+    config: RecorderConfig,
+    stop: Arc<Notify>,
+) -> Result<(), String> {
+    let filter = config.filter.clone().compile()?;
+    let mut file = RotatingFile::open(config).await?;
+    let mut syslog = Some(syslog);
+    let mut previous: Option<SyslogLine> = None;
+
+    file.note("recording started").await?;
+
+    loop {
+        let mut relay = match syslog.take() {
+            Some(relay) => relay,
+            None => {
+                let lost_at = Instant::now();
+
+                // the first try covers the relay dropping while the device stayed connected,
+                // lockdownd also takes a moment to come up after the device is back
+                let relay = loop {
+                    if let Ok(relay) = reconnect_syslog(&device).await {
+                        break Some(relay);
+                    }
+
+                    tokio::select! {
+                        _ = stop.notified() => break None,
+                        _ = sleep(RECONNECT_INTERVAL) => {}
+                    }
+                };
+
+                let Some(relay) = relay else {
+                    file.note("recording stopped").await?;
+                    return Ok(());
+                };
+
+                file.note(&format!(
+                    "syslog resumed after a gap of {}s",
+                    lost_at.elapsed().as_secs()
+                ))
+                .await?;
+
+                relay
+            }
+        };
+
+        loop {
+            let raw = tokio::select! {
+                _ = stop.notified() => {
+                    file.note("recording stopped").await?;
+                    return Ok(());
+                }
+                raw = relay.next() => raw,
+            };
+
+            let raw = match raw {
+                Ok(raw) => raw,
+                Err(e) => {
+                    file.note(&format!("syslog connection lost: {e:?}")).await?;
+                    break;
+                }
+            };
+
+            for raw in raw.lines().filter(|l| !l.trim().is_empty()) {
+                let line = SyslogLine::parse(raw, previous.as_ref());
+
+                if filter.matches(&line) {
+                    file.write_line(raw).await?;
+                }
+
+                previous = Some(line);
+            }
+
+            file.flush().await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_the_header() {
+        let line = SyslogLine::parse(
+            "Oct 19 01:24:39 iPhone SpringBoard(FrontBoard)[57] <Notice>: scene created",
+            None,
+        );
+
+        assert_eq!(line.timestamp, "Oct 19 01:24:39");
+        assert_eq!(line.device, "iPhone");
+        assert_eq!(line.process, "SpringBoard(FrontBoard)");
+        assert_eq!(line.process_name(), "SpringBoard");
+        assert_eq!(line.pid, 57);
+        assert_eq!(line.level, LogLevel::Notice);
+        assert_eq!(line.message, "scene created");
+    }
+
+    #[test]
+    fn parse_keeps_the_header_of_continuations() {
+        let first = SyslogLine::parse(
+            "Oct  2 09:00:01 iPad wifid[120] <Error>: scan failed:",
+            None,
+        );
+        let next = SyslogLine::parse("    reason: busy", Some(&first));
+
+        assert_eq!(next.process, "wifid");
+        assert_eq!(next.pid, 120);
+        assert_eq!(next.level, LogLevel::Error);
+        assert_eq!(next.message, "    reason: busy");
+    }
+
+    #[test]
+    fn parse_treats_malformed_headers_as_continuations() {
+        for line in [
+            "",
+            "Oct 19 01:24:39",
+            "Oct 19 01:24:39 iPhone SpringBoard[abc] <Notice>: bad pid",
+            "Oct 19 01:24:39 iPhone SpringBoard <Notice>: no pid",
+            "Oct 19 01:24:39 iPhone SpringBoard[57] Notice: no brackets",
+            "Oct 19 01:24:3é iPhone SpringBoard[57] <Notice>: not a char boundary",
+        ] {
+            let parsed = SyslogLine::parse(line, None);
+
+            assert_eq!(parsed.message, line);
+            assert_eq!(parsed.pid, 0);
+            assert_eq!(parsed.level, LogLevel::Unknown);
+        }
+    }
+
+    #[test]
+    fn parse_maps_unknown_levels_to_unknown() {
+        let line = SyslogLine::parse("Oct 19 01:24:39 iPhone kernel[0] <Verbose>: hi", None);
+
+        assert_eq!(line.level, LogLevel::Unknown);
+        assert_eq!(line.message, "hi");
+    }
+}
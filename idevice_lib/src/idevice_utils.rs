@@ -1,7 +1,10 @@
+use std::cmp::Ordering as CmpOrdering;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use idevice::{lockdown::LockdownClient, plist_macro::PlistConvertible};
-use plist::Value;
+use base64::{Engine as _, prelude::BASE64_STANDARD};
+use idevice::{Idevice, IdeviceError, lockdown::LockdownClient, plist_macro::PlistConvertible};
+use plist::{Dictionary, Value};
 
 pub async fn get_string_value_or_default(
     device: &mut LockdownClient,
@@ -62,3 +65,280 @@ pub fn value_to_dict(value: plist::Value) -> HashMap<String, String> {
 
     output
 }
+
+/// converts a plist value into json, nested dictionaries and arrays are kept as is
+pub fn plist_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Dictionary(dict) => serde_json::Value::Object(
+            dict.iter()
+                .map(|(k, v)| (k.clone(), plist_to_json(v)))
+                .collect(),
+        ),
+        Value::Array(arr) => serde_json::Value::Array(arr.iter().map(plist_to_json).collect()),
+        Value::Boolean(b) => serde_json::Value::Bool(*b),
+        Value::String(s) => serde_json::Value::String(s.clone()),
+        Value::Integer(i) => match i.as_unsigned() {
+            Some(u) => u.into(),
+            None => i.as_signed().unwrap_or_default().into(),
+        },
+        Value::Real(r) => serde_json::Number::from_f64(*r)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        Value::Date(d) => serde_json::Value::String(d.to_xml_format()),
+        Value::Data(bytes) => {
+            serde_json::Value::String(bytes.iter().map(|b| format!("{b:02x}")).collect())
+        }
+        Value::Uid(uid) => uid.get().into(),
+        _ => serde_json::Value::Null,
+    }
+}
+
+/// like `plist_to_json` but keeps what json can't tell apart, data becomes `{"$data": base64}`,
+/// dates `{"$date": rfc3339}` and uids `{"$uid": n}`, reals always keep their fraction
+pub fn plist_to_lossless_json(value: &Value) -> serde_json::Value {
+    let tagged = |tag: &str, v: serde_json::Value| {
+        serde_json::Value::Object([(tag.to_string(), v)].into_iter().collect())
+    };
+
+    match value {
+        Value::Dictionary(dict) => serde_json::Value::Object(
+            dict.iter()
+                .map(|(k, v)| (k.clone(), plist_to_lossless_json(v)))
+                .collect(),
+        ),
+        Value::Array(arr) => {
+            serde_json::Value::Array(arr.iter().map(plist_to_lossless_json).collect())
+        }
+        Value::Real(r) if !r.is_finite() => tagged("$real", r.to_string().into()),
+        Value::Date(d) => tagged("$date", d.to_xml_format().into()),
+        Value::Data(bytes) => tagged("$data", BASE64_STANDARD.encode(bytes).into()),
+        Value::Uid(uid) => tagged("$uid", uid.get().into()),
+        other => plist_to_json(other),
+    }
+}
+
+/// the inverse of `plist_to_lossless_json`, objects with a single `$data`, `$date`, `$uid` or
+/// `$real` key become those types again
+pub fn lossless_json_to_plist(value: &serde_json::Value) -> Result<Value, String> {
+    match value {
+        serde_json::Value::Object(obj) => {
+            if let (1, Some((tag, v))) = (obj.len(), obj.iter().next()) {
+                match (tag.as_str(), v) {
+                    ("$data", serde_json::Value::String(b64)) => {
+                        return BASE64_STANDARD
+                            .decode(b64)
+                            .map(Value::Data)
+                            .map_err(|e| format!("bad $data {b64:?}: {e}"));
+                    }
+                    ("$date", serde_json::Value::String(date)) => {
+                        return plist::Date::from_xml_format(date)
+                            .map(Value::Date)
+                            .map_err(|e| format!("bad $date {date:?}: {e}"));
+                    }
+                    ("$uid", serde_json::Value::Number(n)) => {
+                        return n
+                            .as_u64()
+                            .map(|uid| Value::Uid(plist::Uid::new(uid)))
+                            .ok_or_else(|| format!("bad $uid {n}"));
+                    }
+                    ("$real", serde_json::Value::String(r)) => {
+                        return r
+                            .parse::<f64>()
+                            .map(Value::Real)
+                            .map_err(|e| format!("bad $real {r:?}: {e}"));
+                    }
+                    _ => {}
+                }
+            }
+
+            obj.iter()
+                .map(|(k, v)| Ok((k.clone(), lossless_json_to_plist(v)?)))
+                .collect::<Result<Dictionary, String>>()
+                .map(Value::Dictionary)
+        }
+        serde_json::Value::Array(arr) => arr
+            .iter()
+            .map(lossless_json_to_plist)
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        serde_json::Value::Bool(b) => Ok(Value::Boolean(*b)),
+        serde_json::Value::String(s) => Ok(Value::String(s.clone())),
+        serde_json::Value::Number(n) => Ok(match (n.as_u64(), n.as_i64(), n.as_f64()) {
+            (Some(u), _, _) => Value::Integer(u.into()),
+            (_, Some(i), _) => Value::Integer(i.into()),
+            (_, _, Some(r)) => Value::Real(r),
+            _ => return Err(format!("unsupported number {n}")),
+        }),
+        serde_json::Value::Null => Err("plist has no null".into()),
+    }
+}
+
+/// shared with the host so it can stop a long running operation from another thread
+#[derive(Debug, Default)]
+pub struct CancelToken(AtomicBool);
+
+impl CancelToken {
+    pub const fn new() -> Self {
+        Self(AtomicBool::new(false))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// compares dotted versions like `16.4.1` numerically, missing parts count as zero
+pub fn compare_versions(a: &str, b: &str) -> CmpOrdering {
+    let parse = |v: &str| {
+        v.split('.')
+            .map(|p| p.trim().parse::<u32>().unwrap_or_default())
+            .collect::<Vec<_>>()
+    };
+    let (a, b) = (parse(a), parse(b));
+
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            a.get(i)
+                .copied()
+                .unwrap_or_default()
+                .cmp(&b.get(i).copied().unwrap_or_default())
+        })
+        .find(|o| o.is_ne())
+        .unwrap_or(CmpOrdering::Equal)
+}
+
+/// `Idevice::send_plist` is private, this frames the message the same way: a big endian
+/// length followed by the xml plist
+pub async fn send_plist(idevice: &mut Idevice, message: Value) -> Result<(), IdeviceError> {
+    let mut buf = Vec::new();
+    message.to_writer_xml(&mut buf)?;
+
+    idevice.send_raw(&(buf.len() as u32).to_be_bytes()).await?;
+    idevice.send_raw(&buf).await
+}
+
+/// reads one length prefixed plist, unlike `Idevice::read_plist` it doesn't have to be a
+/// dictionary and an `Error` key is left for the caller to look at
+pub async fn read_plist_value(idevice: &mut Idevice) -> Result<Value, IdeviceError> {
+    let len = idevice.read_raw(4).await?;
+    let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+
+    let data = idevice.read_raw(len).await?;
+    Ok(plist::from_bytes(&data)?)
+}
+
+pub async fn read_plist(idevice: &mut Idevice) -> Result<Dictionary, IdeviceError> {
+    match read_plist_value(idevice).await? {
+        Value::Dictionary(dict) => Ok(dict),
+        _ => Err(IdeviceError::UnexpectedResponse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_versions_orders_numerically() {
+        assert_eq!(compare_versions("17.0", "16.7.2"), CmpOrdering::Greater);
+        assert_eq!(compare_versions("16.10", "16.9"), CmpOrdering::Greater);
+        assert_eq!(compare_versions("15.4.1", "15.5"), CmpOrdering::Less);
+    }
+
+    #[test]
+    fn compare_versions_pads_missing_components() {
+        assert_eq!(compare_versions("17", "17.0.0"), CmpOrdering::Equal);
+        assert_eq!(compare_versions("17.0.1", "17"), CmpOrdering::Greater);
+    }
+
+    #[test]
+    fn compare_versions_reads_malformed_components_as_zero() {
+        assert_eq!(compare_versions("", "0"), CmpOrdering::Equal);
+        assert_eq!(compare_versions("16.beta", "16.0"), CmpOrdering::Equal);
+        assert_eq!(compare_versions(" 16 . 2 ", "16.2"), CmpOrdering::Equal);
+        assert_eq!(compare_versions("16.x.1", "16.0.2"), CmpOrdering::Less);
+    }
+
+    #[test]
+    fn lossless_json_round_trips_a_plist() {
+        let mut dict = Dictionary::new();
+        dict.insert("name".into(), Value::String("Dock".into()));
+        dict.insert("count".into(), Value::Integer((-3).into()));
+        dict.insert("scale".into(), Value::Real(2.0));
+        dict.insert("infinite".into(), Value::Real(f64::INFINITY));
+        dict.insert("hidden".into(), Value::Boolean(false));
+        dict.insert("icon".into(), Value::Data(vec![0, 1, 254, 255]));
+        dict.insert(
+            "modified".into(),
+            Value::Date(plist::Date::from_xml_format("2024-05-01T12:30:00Z").unwrap()),
+        );
+        dict.insert("ref".into(), Value::Uid(plist::Uid::new(7)));
+        let value = Value::Array(vec![Value::Dictionary(dict), Value::Array(vec![])]);
+
+        let json = serde_json::to_string(&plist_to_lossless_json(&value)).unwrap();
+        let back = lossless_json_to_plist(&serde_json::from_str(&json).unwrap()).unwrap();
+
+        assert_eq!(back, value);
+    }
+
+    #[test]
+    fn lossless_json_rejects_what_plist_cannot_hold() {
+        assert!(lossless_json_to_plist(&serde_json::json!(null)).is_err());
+        assert!(lossless_json_to_plist(&serde_json::json!({"$data": "not base64!"})).is_err());
+        assert!(lossless_json_to_plist(&serde_json::json!({"$date": "yesterday"})).is_err());
+    }
+
+    #[test]
+    fn plist_to_lossless_json_tags_what_json_cannot_hold() {
+        let mut dict = Dictionary::new();
+        dict.insert("Data".into(), Value::Data(b"hi".to_vec()));
+        dict.insert(
+            "Date".into(),
+            Value::Date(plist::Date::from_xml_format("2024-05-01T12:30:00Z").unwrap()),
+        );
+        dict.insert("Uid".into(), Value::Uid(plist::Uid::new(3)));
+        dict.insert("Real".into(), Value::Real(2.0));
+        dict.insert("Negative".into(), Value::Integer((-5).into()));
+        dict.insert("Large".into(), Value::Integer(u64::MAX.into()));
+        dict.insert(
+            "Nested".into(),
+            Value::Array(vec![Value::Boolean(true), Value::String("x".into())]),
+        );
+
+        let json = plist_to_lossless_json(&Value::Dictionary(dict));
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "Data": {"$data": "aGk="},
+                "Date": {"$date": "2024-05-01T12:30:00Z"},
+                "Uid": {"$uid": 3},
+                "Real": 2.0,
+                "Negative": -5,
+                "Large": u64::MAX,
+                "Nested": [true, "x"],
+            })
+        );
+        assert!(json["Real"].is_f64());
+    }
+
+    #[test]
+    fn plist_to_lossless_json_keeps_reals_json_has_no_number_for() {
+        assert_eq!(
+            plist_to_lossless_json(&Value::Real(f64::NAN)),
+            serde_json::json!({"$real": "NaN"})
+        );
+        assert_eq!(
+            plist_to_lossless_json(&Value::Real(f64::NEG_INFINITY)),
+            serde_json::json!({"$real": "-inf"})
+        );
+        assert_eq!(
+            plist_to_lossless_json(&Value::Data(vec![])),
+            serde_json::json!({"$data": ""})
+        );
+    }
+}
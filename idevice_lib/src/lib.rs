@@ -1,28 +1,43 @@
+mod idevice_apps;
+mod idevice_config;
+mod idevice_crash;
+mod idevice_diag;
 mod idevice_fs;
 mod idevice_helper;
+mod idevice_ipa;
+mod idevice_models;
+mod idevice_pairing;
+mod idevice_provider;
+mod idevice_region;
+mod idevice_screenshot;
+mod idevice_settings;
+mod idevice_springboard;
+mod idevice_syslog;
 mod idevice_utils;
 
 use idevice::IdeviceService;
 use idevice::afc::AfcClient;
 use idevice::lockdown::LockdownClient;
-use idevice::provider::UsbmuxdProvider;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::runtime::{self, Runtime};
+use tokio::sync::Notify;
 use tokio::time::sleep;
 
 use std::any::Any;
 use std::collections::VecDeque;
 use std::ffi::{CString, c_char, c_int, c_void};
-use std::sync::{LazyLock, Mutex};
+use std::sync::{Arc, LazyLock, Mutex, Once};
 use std::time::Duration;
 use std::{panic, ptr};
 
+use crate::idevice_apps::{AppCommand, AppFilter, InstallPhase};
+use crate::idevice_diag::PowerAction;
 use crate::idevice_fs::{FSTreeFFI, convert_to_ffi, list_path};
-use crate::idevice_helper::{
-    Event, RawHardware, RawOS, get_lockdownd_client, handle_device_battery, handle_device_hardware,
-    handle_device_os, handle_device_storage,
-};
-use crate::idevice_utils::value_to_dict;
+use crate::idevice_helper::{ConnectionKind, Event, get_lockdownd_client, get_syslog_client};
+use crate::idevice_provider::DeviceProvider;
+use crate::idevice_settings::{SetValueError, SetValueStatus};
+use crate::idevice_syslog::{RecorderConfig, SyslogFilter, SyslogLineFFI, with_ffi_line};
+use crate::idevice_utils::{CancelToken, value_to_dict};
 
 #[repr(C)]
 pub struct RustResult {
@@ -30,6 +45,123 @@ pub struct RustResult {
     pub err: *mut c_char,
 }
 
+/// bytes owned by rust, the host must give them back with `free_byte_buffer`
+#[repr(C)]
+pub struct ByteBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl ByteBuffer {
+    fn from_vec(data: Vec<u8>) -> *mut Self {
+        let data = data.into_boxed_slice();
+        let len = data.len();
+
+        Box::into_raw(Box::new(Self {
+            data: Box::into_raw(data) as *mut u8,
+            len,
+        }))
+    }
+}
+
+fn bytes_result(res: Result<Vec<u8>, String>) -> RustResult {
+    match res {
+        Ok(bytes) => RustResult {
+            ok: ByteBuffer::from_vec(bytes) as *mut c_void,
+            err: ptr::null_mut(),
+        },
+        Err(e) => err_result(e),
+    }
+}
+
+fn err_result(e: String) -> RustResult {
+    RustResult {
+        ok: ptr::null_mut(),
+        err: CString::new(e).unwrap().into_raw(),
+    }
+}
+
+fn json_result<T: serde::Serialize>(res: Result<T, String>) -> RustResult {
+    match res.and_then(|v| serde_json::to_string(&v).map_err(|e| e.to_string())) {
+        Ok(json) => RustResult {
+            ok: CString::new(json).unwrap().into_raw() as *mut c_void,
+            err: ptr::null_mut(),
+        },
+        Err(e) => err_result(e),
+    }
+}
+
+/// reads an optional json argument, a null pointer gives the default value
+fn json_arg<T: serde::de::DeserializeOwned + Default>(arg: *const c_char) -> Result<T, String> {
+    if arg.is_null() {
+        return Ok(T::default());
+    }
+
+    let arg = unsafe { std::ffi::CStr::from_ptr(arg) }
+        .to_str()
+        .map_err(|e| format!("argument is not valid utf-8: {e}"))?;
+
+    serde_json::from_str(arg).map_err(|e| format!("bad json argument: {e}"))
+}
+
+fn str_arg(arg: *const c_char) -> Result<String, String> {
+    if arg.is_null() {
+        return Err("argument is null".into());
+    }
+
+    unsafe { std::ffi::CStr::from_ptr(arg) }
+        .to_str()
+        .map(ToString::to_string)
+        .map_err(|e| format!("argument is not valid utf-8: {e}"))
+}
+
+/// the host owns the provider until it passes it to `free_provider`
+fn provider_arg(provider: *mut DeviceProvider) -> Result<&'static DeviceProvider, String> {
+    unsafe { provider.as_ref() }.ok_or_else(|| "provider is null".into())
+}
+
+/// the token lives until the host passes it to `free_cancel_token`
+fn cancel_arg(token: *const CancelToken) -> Option<&'static CancelToken> {
+    unsafe { token.as_ref() }
+}
+
+/// copies the host's buffer, it keeps ownership of it
+fn bytes_arg(data: *const u8, len: usize) -> Result<Vec<u8>, String> {
+    if data.is_null() {
+        return Err("data is null".into());
+    }
+
+    Ok(unsafe { std::slice::from_raw_parts(data, len) }.to_vec())
+}
+
+/// takes back something handed to the host with `Box::into_raw`, null gives `None`
+fn take_box<T: ?Sized>(ptr: *mut T) -> Option<Box<T>> {
+    (!ptr.is_null()).then(|| unsafe { Box::from_raw(ptr) })
+}
+
+/// unwraps the result, or returns its error from the export
+macro_rules! ffi_try {
+    ($arg:expr) => {
+        ffi_try!($arg, err_result)
+    };
+    ($arg:expr, $on_err:expr) => {
+        match $arg {
+            Ok(arg) => arg,
+            Err(e) => return $on_err(e),
+        }
+    };
+}
+
+/// progress in percent and a status string that's only valid during the call
+pub type StatusCallback = extern "C" fn(u8, *const c_char);
+
+fn report_status(callback: StatusCallback) -> impl FnMut(u8, &str) {
+    move |progress, status| {
+        let status = CString::new(status).unwrap_or_default();
+        callback(progress, status.as_ptr());
+    }
+}
+
 static GLOBAL_RUNTIME: LazyLock<Runtime> = LazyLock::new(|| {
     runtime::Builder::new_multi_thread()
         .enable_io()
@@ -44,8 +176,14 @@ where
     R: Send + 'static,
 {
     let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let operations = idevice_config::operation_limit();
 
     GLOBAL_RUNTIME.handle().spawn(async move {
+        // held until the call is done, the semaphore is never closed
+        let _permit = match operations {
+            Some(operations) => operations.acquire_owned().await.ok(),
+            None => None,
+        };
         let res = fut.await;
         // best-effort send; ignore if receiver dropped
         let _ = tx.send(res);
@@ -77,8 +215,15 @@ pub extern "C" fn poll_event(out_event: *mut c_int) -> bool {
     }
 }
 
+static DEVICE_WATCHER: Once = Once::new();
+
+/// starting it more than once keeps the first watcher
 #[unsafe(no_mangle)]
 pub extern "C" fn start_device_watcher() {
+    DEVICE_WATCHER.call_once(spawn_device_watcher);
+}
+
+fn spawn_device_watcher() {
     GLOBAL_RUNTIME.spawn(async move {
         let mut was_connected = false;
 
@@ -104,8 +249,8 @@ pub extern "C" fn start_device_watcher() {
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn get_device_info(provider: *mut UsbmuxdProvider) -> RustResult {
-    let provider = unsafe { &(*provider) };
+pub extern "C" fn get_device_info(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
     let device_info = match run_sync(async move {
         let mut lockdownd = get_lockdownd_client(provider).await?;
         lockdownd
@@ -132,10 +277,787 @@ pub extern "C" fn get_device_info(provider: *mut UsbmuxdProvider) -> RustResult
     }
 }
 
+/// `filter` is an optional json `AppFilter`, e.g. `{"app_type": "User", "attributes": ["Entitlements"]}`
+#[unsafe(no_mangle)]
+pub extern "C" fn list_apps(provider: *mut DeviceProvider, filter: *const c_char) -> RustResult {
+    let filter: AppFilter = ffi_try!(json_arg(filter));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_apps::list_apps(provider, filter)))
+}
+
+fn install_path_ffi(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+    command: AppCommand,
+    callback: StatusCallback,
+) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        let mut report_upload = report_status(callback);
+
+        idevice_apps::install_path(
+            provider,
+            path,
+            command,
+            &NEVER_CANCELLED,
+            move |progress| report_upload(progress, "Uploading"),
+            report_status(callback),
+        )
+        .await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn install_app_from_path(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+    callback: StatusCallback,
+) -> RustResult {
+    install_path_ffi(provider, path, AppCommand::Install, callback)
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn upgrade_app_from_path(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+    callback: StatusCallback,
+) -> RustResult {
+    install_path_ffi(provider, path, AppCommand::Upgrade, callback)
+}
+
+/// the phase, its progress in percent and a status string that's only valid during the call
+pub type InstallCallback = extern "C" fn(InstallPhase, u8, *const c_char);
+
+static NEVER_CANCELLED: CancelToken = CancelToken::new();
+
+/// streams the package at `path` to the device instead of loading it into memory,
+/// `cancel` may be null, otherwise it comes from `create_cancel_token`. cancelling stops the
+/// upload, once installation_proxy is installing the package it can't be cancelled anymore
+#[unsafe(no_mangle)]
+pub extern "C" fn install_app_streaming(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+    upgrade: bool,
+    cancel: *const CancelToken,
+    callback: InstallCallback,
+) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let provider = ffi_try!(provider_arg(provider));
+    let cancel = cancel_arg(cancel).unwrap_or(&NEVER_CANCELLED);
+    let command = if upgrade {
+        AppCommand::Upgrade
+    } else {
+        AppCommand::Install
+    };
+
+    json_result(run_sync(async move {
+        idevice_apps::install_path(
+            provider,
+            path,
+            command,
+            cancel,
+            move |progress| callback(InstallPhase::Upload, progress, c"Uploading".as_ptr()),
+            move |progress, status| {
+                let status = CString::new(status).unwrap_or_default();
+                callback(InstallPhase::Install, progress, status.as_ptr());
+            },
+        )
+        .await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn create_cancel_token() -> *mut CancelToken {
+    Box::into_raw(Box::new(CancelToken::new()))
+}
+
+/// safe to call from any thread while the operation using the token is running
+#[unsafe(no_mangle)]
+pub extern "C" fn cancel_token(token: *const CancelToken) {
+    if let Some(token) = cancel_arg(token) {
+        token.cancel();
+    }
+}
+
+/// the token must not be used by a running operation anymore
+#[unsafe(no_mangle)]
+pub extern "C" fn free_cancel_token(token: *mut CancelToken) {
+    drop(take_box(token));
+}
+
+/// `data` is copied before the call returns, the host keeps ownership of it
+#[unsafe(no_mangle)]
+pub extern "C" fn install_app_from_bytes(
+    provider: *mut DeviceProvider,
+    data: *const u8,
+    len: usize,
+    callback: StatusCallback,
+) -> RustResult {
+    let data = ffi_try!(bytes_arg(data, len));
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        let mut report_upload = report_status(callback);
+
+        idevice_apps::install_bytes(
+            provider,
+            data,
+            AppCommand::Install,
+            move |progress| report_upload(progress, "Uploading"),
+            report_status(callback),
+        )
+        .await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn uninstall_app(
+    provider: *mut DeviceProvider,
+    bundle_id: *const c_char,
+    callback: StatusCallback,
+) -> RustResult {
+    let bundle_id = ffi_try!(str_arg(bundle_id));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_apps::uninstall(provider, &bundle_id, report_status(callback)).await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn archive_app(
+    provider: *mut DeviceProvider,
+    bundle_id: *const c_char,
+    skip_uninstall: bool,
+    callback: StatusCallback,
+) -> RustResult {
+    let bundle_id = ffi_try!(str_arg(bundle_id));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_apps::archive(
+            provider,
+            &bundle_id,
+            skip_uninstall,
+            report_status(callback),
+        )
+        .await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn restore_app_archive(
+    provider: *mut DeviceProvider,
+    bundle_id: *const c_char,
+    callback: StatusCallback,
+) -> RustResult {
+    let bundle_id = ffi_try!(str_arg(bundle_id));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_apps::restore_archive(provider, &bundle_id, report_status(callback)).await
+    }))
+}
+
+/// `provider` may be null to only read the ipa, otherwise the result also carries warnings
+/// about why it wouldn't install on that device
+#[unsafe(no_mangle)]
+pub extern "C" fn inspect_ipa(path: *const c_char, provider: *mut DeviceProvider) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let mut ipa = ffi_try!(idevice_ipa::read_ipa(path));
+
+    let Ok(provider) = provider_arg(provider) else {
+        return json_result(Ok(ipa));
+    };
+
+    json_result(run_sync(async move {
+        idevice_ipa::check_compatibility(provider, &mut ipa).await?;
+        Ok(ipa)
+    }))
+}
+
+/// the icon is a png in a `ByteBuffer`
+#[unsafe(no_mangle)]
+pub extern "C" fn get_app_icon(
+    provider: *mut DeviceProvider,
+    bundle_id: *const c_char,
+) -> RustResult {
+    let bundle_id = ffi_try!(str_arg(bundle_id));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    bytes_result(run_sync(async move {
+        idevice_springboard::get_app_icon(provider, &bundle_id).await
+    }))
+}
+
+/// the home screen wallpaper as a png in a `ByteBuffer`
+#[unsafe(no_mangle)]
+pub extern "C" fn get_wallpaper(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    bytes_result(run_sync(idevice_springboard::get_wallpaper(provider)))
+}
+
+/// `path` ending with `.plist` saves a plist, anything else saves json
+#[unsafe(no_mangle)]
+pub extern "C" fn export_home_screen_layout(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_springboard::export_layout(provider, path).await
+    }))
+}
+
+/// returns a json `LayoutImport`, `skip_missing` leaves out the apps that aren't installed
+/// instead of failing
+#[unsafe(no_mangle)]
+pub extern "C" fn import_home_screen_layout(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+    skip_missing: bool,
+) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_springboard::import_layout(provider, path, skip_missing).await
+    }))
+}
+
+pub type SyslogCallback = extern "C" fn(*const SyslogLineFFI);
+
+/// a running syslog stream, stopped and freed by `stop_syslog_stream`
+pub struct SyslogStream {
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// `filter` is an optional json `SyslogFilter`, e.g.
+/// `{"processes": ["SpringBoard"], "regex": "wifi", "min_level": "Warning"}`.
+/// `callback` is called from a runtime thread for every matching line
+#[unsafe(no_mangle)]
+pub extern "C" fn start_syslog_stream(
+    provider: *mut DeviceProvider,
+    filter: *const c_char,
+    callback: SyslogCallback,
+) -> RustResult {
+    let filter = ffi_try!(json_arg::<SyslogFilter>(filter).and_then(SyslogFilter::compile));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    // connect before returning so a missing device is reported to the host right away
+    let syslog = ffi_try!(run_sync(get_syslog_client(provider)));
+
+    let task = GLOBAL_RUNTIME.spawn(async move {
+        let _ = idevice_syslog::stream_syslog(syslog, filter, |line| {
+            with_ffi_line(line, |line| callback(line))
+        })
+        .await;
+    });
+
+    RustResult {
+        ok: Box::into_raw(Box::new(SyslogStream { task })) as *mut c_void,
+        err: ptr::null_mut(),
+    }
+}
+
+/// stops the stream, a callback that is already running still finishes
+#[unsafe(no_mangle)]
+pub extern "C" fn stop_syslog_stream(stream: *mut SyslogStream) {
+    if let Some(stream) = take_box(stream) {
+        stream.task.abort();
+    }
+}
+
+/// a running syslog recording, stopped and freed by `stop_syslog_recorder`
+pub struct SyslogRecorder {
+    stop: Arc<Notify>,
+    task: tokio::task::JoinHandle<Result<(), String>>,
+}
+
+/// `config` is a json `RecorderConfig`, e.g.
+/// `{"path": "C:/logs/device.log", "max_bytes": 104857600, "keep_files": 10}`.
+/// the recording survives the device reconnecting and picks up the same device again
+#[unsafe(no_mangle)]
+pub extern "C" fn start_syslog_recorder(
+    provider: *mut DeviceProvider,
+    config: *const c_char,
+) -> RustResult {
+    let config: RecorderConfig = ffi_try!(json_arg(config));
+
+    if config.path.as_os_str().is_empty() {
+        return err_result("the recorder needs a path".into());
+    }
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    let syslog = ffi_try!(run_sync(get_syslog_client(provider)));
+    // the host may free its provider while the recording still needs the device
+    let device = ffi_try!(run_sync(provider.find_again()));
+
+    let stop = Arc::new(Notify::new());
+    let task = GLOBAL_RUNTIME.spawn(idevice_syslog::record_syslog(
+        device,
+        syslog,
+        config,
+        stop.clone(),
+    ));
+
+    RustResult {
+        ok: Box::into_raw(Box::new(SyslogRecorder { stop, task })) as *mut c_void,
+        err: ptr::null_mut(),
+    }
+}
+
+/// waits for the file to be flushed and returns the error that ended the recording early,
+/// if there was one
+#[unsafe(no_mangle)]
+pub extern "C" fn stop_syslog_recorder(recorder: *mut SyslogRecorder) -> RustResult {
+    let recorder = ffi_try!(take_box(recorder).ok_or_else(|| "recorder is null".to_string()));
+    recorder.stop.notify_one();
+
+    json_result(run_sync(async move {
+        recorder
+            .task
+            .await
+            .map_err(|e| format!("the recorder panicked: {e:?}"))?
+    }))
+}
+
+/// `dir` may be null to list the top of the crash report directory. the listed paths are
+/// relative to that top, ready for `download_crash_report` and `delete_crash_report`
+#[unsafe(no_mangle)]
+pub extern "C" fn list_crash_reports(
+    provider: *mut DeviceProvider,
+    dir: *const c_char,
+) -> RustResult {
+    let dir = ffi_try!(optional_str_arg(dir));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_crash::list_crash_reports(provider, dir.as_deref()).await
+    }))
+}
+
+/// `delete` removes the report from the device once it's saved to `dest`
+#[unsafe(no_mangle)]
+pub extern "C" fn download_crash_report(
+    provider: *mut DeviceProvider,
+    name: *const c_char,
+    dest: *const c_char,
+    delete: bool,
+) -> RustResult {
+    let (name, dest) = ffi_try!(str_arg(name).and_then(|n| Ok((n, str_arg(dest)?))));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_crash::download_crash_report(provider, &name, dest, delete).await
+    }))
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn delete_crash_report(
+    provider: *mut DeviceProvider,
+    name: *const c_char,
+) -> RustResult {
+    let name = ffi_try!(str_arg(name));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_crash::delete_crash_report(provider, &name).await
+    }))
+}
+
+/// parses a downloaded `.ips` report into a json `CrashReport`
+#[unsafe(no_mangle)]
+pub extern "C" fn parse_crash_report(path: *const c_char) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    json_result(
+        std::fs::read(&path)
+            .map_err(|e| format!("failed to read {path}: {e:?}"))
+            .and_then(|data| idevice_crash::parse_ips(&data)),
+    )
+}
+
+/// `action` is a `PowerAction`. with `wait` a restart or shutdown returns once the device
+/// disconnected, and a restart once it's back, or fails after `timeout_secs`, which can't be 0
+/// then. sleeping doesn't wait
+#[unsafe(no_mangle)]
+pub extern "C" fn device_power_action(
+    provider: *mut DeviceProvider,
+    action: u32,
+    wait: bool,
+    timeout_secs: u32,
+) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+    let action = ffi_try!(PowerAction::try_from(action));
+
+    json_result(run_sync(idevice_diag::power_action(
+        provider,
+        action,
+        wait,
+        Duration::from_secs(timeout_secs.into()),
+    )))
+}
+
+fn optional_str_arg(arg: *const c_char) -> Result<Option<String>, String> {
+    if arg.is_null() {
+        Ok(None)
+    } else {
+        str_arg(arg).map(Some)
+    }
+}
+
+/// every argument may be null, at least one should be set. data, dates and uids come back
+/// tagged as `{"$data": ..}`, `{"$date": ..}` and `{"$uid": ..}`
+#[unsafe(no_mangle)]
+pub extern "C" fn query_ioregistry(
+    provider: *mut DeviceProvider,
+    plane: *const c_char,
+    name: *const c_char,
+    class: *const c_char,
+) -> RustResult {
+    let plane = ffi_try!(optional_str_arg(plane));
+    let name = ffi_try!(optional_str_arg(name));
+    let class = ffi_try!(optional_str_arg(class));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_diag::query_ioregistry(
+            provider,
+            plane.as_deref(),
+            name.as_deref(),
+            class.as_deref(),
+        )
+        .await
+    }))
+}
+
+/// `keys` is a json array of mobilegestalt keys, e.g. `["ProductType", "ChipID"]`
+#[unsafe(no_mangle)]
+pub extern "C" fn query_mobilegestalt(
+    provider: *mut DeviceProvider,
+    keys: *const c_char,
+) -> RustResult {
+    let keys: Vec<String> = ffi_try!(json_arg(keys));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_diag::query_mobilegestalt(provider, keys)))
+}
+
+/// a json `BatteryReport`, check its `version` before reading the fields
+#[unsafe(no_mangle)]
+pub extern "C" fn get_battery_report(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_battery_report(
+        provider,
+    )))
+}
+
+/// a json `Storage` with the total, used and available bytes
+#[unsafe(no_mangle)]
+pub extern "C" fn get_device_storage(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_storage(provider)))
+}
+
+/// a json `StorageBreakdown`, every size is in bytes
+#[unsafe(no_mangle)]
+pub extern "C" fn get_storage_breakdown(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_storage_breakdown(
+        provider,
+    )))
+}
+
+/// `id` is a product type like `iPhone14,2` or a board id like `D63AP`, the json `DeviceModel`
+/// is `null` for models the table doesn't know
+#[unsafe(no_mangle)]
+pub extern "C" fn lookup_device_model(id: *const c_char) -> RustResult {
+    json_result(str_arg(id).map(|id| idevice_models::lookup_model(&id)))
+}
+
+/// the json `DeviceModel` of the connected device, `null` when the table doesn't know it
+#[unsafe(no_mangle)]
+pub extern "C" fn get_device_model(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_models::get_device_model(provider)))
+}
+
+/// decodes a `ModelNumber` like `MLPF2` and a `RegionInfo` like `LL/A` into a json
+/// `ModelNumberInfo`, no device needed
+#[unsafe(no_mangle)]
+pub extern "C" fn decode_model_number(
+    model_number: *const c_char,
+    region_code: *const c_char,
+) -> RustResult {
+    json_result(str_arg(model_number).and_then(|model_number| {
+        let region_code = str_arg(region_code)?;
+        Ok(idevice_region::decode_model_number(
+            &model_number,
+            &region_code,
+        ))
+    }))
+}
+
+/// a json `HardwareInfo`, the product type with the device's model number, purchase type and
+/// region decoded
+#[unsafe(no_mangle)]
+pub extern "C" fn get_device_hardware(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_hardware_info(
+        provider,
+    )))
+}
+
+/// a json `DeviceIdentity`, read in one lockdownd session
+#[unsafe(no_mangle)]
+pub extern "C" fn get_device_identity(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_identity(provider)))
+}
+
+/// a json `DeviceStatus` with the activation, lock and supervision state
+#[unsafe(no_mangle)]
+pub extern "C" fn get_device_status(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_helper::handle_device_status(provider)))
+}
+
+/// `err` is null when `status` is `Ok`, otherwise the host frees it with `free_c_string`
+#[repr(C)]
+pub struct SetValueResult {
+    pub status: SetValueStatus,
+    pub err: *mut c_char,
+}
+
+impl From<Result<(), SetValueError>> for SetValueResult {
+    fn from(value: Result<(), SetValueError>) -> Self {
+        match value {
+            Ok(()) => Self {
+                status: SetValueStatus::Ok,
+                err: ptr::null_mut(),
+            },
+            Err(e) => Self {
+                status: e.status,
+                err: CString::new(e.message).unwrap_or_default().into_raw(),
+            },
+        }
+    }
+}
+
+fn invalid_argument(e: String) -> SetValueResult {
+    Err(SetValueError::new(SetValueStatus::InvalidArgument, e)).into()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn set_device_name(
+    provider: *mut DeviceProvider,
+    name: *const c_char,
+) -> SetValueResult {
+    let name = ffi_try!(str_arg(name), invalid_argument);
+    let provider = ffi_try!(provider_arg(provider), invalid_argument);
+
+    run_sync(async move { idevice_settings::set_device_name(provider, &name).await }).into()
+}
+
+/// `domain` may be null for the default domain, `value` is json like `"name"` or `true`.
+/// only the keys in `WRITABLE_VALUES` are accepted
+#[unsafe(no_mangle)]
+pub extern "C" fn set_device_value(
+    provider: *mut DeviceProvider,
+    domain: *const c_char,
+    key: *const c_char,
+    value: *const c_char,
+) -> SetValueResult {
+    let args = optional_str_arg(domain).and_then(|domain| {
+        let key = str_arg(key)?;
+        let value = serde_json::from_str::<serde_json::Value>(&str_arg(value)?)
+            .map_err(|e| format!("the value is not json: {e}"))?;
+        Ok((domain, key, value))
+    });
+    let (domain, key, value) = ffi_try!(args, invalid_argument);
+    let provider = ffi_try!(provider_arg(provider), invalid_argument);
+
+    run_sync(async move {
+        idevice_settings::set_device_value(provider, domain.as_deref(), &key, &value).await
+    })
+    .into()
+}
+
+/// shows the Trust dialog and waits up to `timeout_secs` for the user to answer.
+/// `Event::WaitingForTrust` is pushed once the device says the dialog is up, or that it has
+/// to be unlocked first
+#[unsafe(no_mangle)]
+pub extern "C" fn pair_device(provider: *mut DeviceProvider, timeout_secs: u32) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_pairing::pair(provider, Duration::from_secs(timeout_secs.into()), || {
+            push_event(Event::WaitingForTrust)
+        })
+        .await
+    }))
+}
+
+/// json `{ valid, reason }`, a missing or rejected pairing record is not an error
+#[unsafe(no_mangle)]
+pub extern "C" fn validate_pairing(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_pairing::validate_pairing(provider)))
+}
+
+/// the device forgets this computer and usbmuxd's record for it is deleted, a provider made
+/// from a pairing file keeps that file
+#[unsafe(no_mangle)]
+pub extern "C" fn unpair_device(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_pairing::unpair(provider)))
+}
+
+/// writes the device's pairing record to `path` so other machines can use the device
+#[unsafe(no_mangle)]
+pub extern "C" fn export_pairing_file(
+    provider: *mut DeviceProvider,
+    path: *const c_char,
+) -> RustResult {
+    let path = ffi_try!(str_arg(path));
+
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(async move {
+        idevice_provider::export_pairing_file(provider, &path).await
+    }))
+}
+
+/// a provider for the device paired by the record at `path`, reached through the usbmuxd at
+/// `socket_address` (`host:port`, or a unix socket path). free it with `free_provider`
+#[unsafe(no_mangle)]
+pub extern "C" fn create_provider_from_pairing_file(
+    path: *const c_char,
+    socket_address: *const c_char,
+) -> RustResult {
+    let args = str_arg(path).and_then(|path| Ok((path, str_arg(socket_address)?)));
+    let (path, socket_address) = ffi_try!(args);
+
+    match run_sync(async move {
+        idevice_provider::provider_from_pairing_file(
+            &path,
+            &socket_address,
+            &idevice_config::label(),
+        )
+        .await
+    }) {
+        Ok(p) => RustResult {
+            ok: Box::into_raw(Box::new(p)) as *mut c_void,
+            err: ptr::null_mut(),
+        },
+        Err(e) => err_result(e),
+    }
+}
+
+/// a provider that connects straight to the device at `ip` with the pairing record at
+/// `pairing_file_path`, for devices usbmuxd doesn't see. free it with `free_provider`
+#[unsafe(no_mangle)]
+pub extern "C" fn create_tcp_provider(
+    ip: *const c_char,
+    pairing_file_path: *const c_char,
+) -> RustResult {
+    let provider = str_arg(ip).and_then(|ip| {
+        idevice_provider::provider_from_ip(
+            &ip,
+            str_arg(pairing_file_path)?,
+            &idevice_config::label(),
+        )
+    });
+
+    match provider {
+        Ok(p) => RustResult {
+            ok: Box::into_raw(Box::new(p)) as *mut c_void,
+            err: ptr::null_mut(),
+        },
+        Err(e) => err_result(e),
+    }
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn get_wifi_connections(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    json_result(run_sync(idevice_settings::get_wifi_connections(provider)))
+}
+
+/// turns Wi-Fi connections on or off. when turning them on for a usbmuxd device,
+/// `Event::NetworkConnected` is pushed once it shows up over the network, if that happens
+/// within `timeout_secs`
+#[unsafe(no_mangle)]
+pub extern "C" fn set_wifi_connections(
+    provider: *mut DeviceProvider,
+    enabled: bool,
+    timeout_secs: u32,
+) -> SetValueResult {
+    let provider = ffi_try!(provider_arg(provider), invalid_argument);
+
+    let res =
+        run_sync(async move { idevice_settings::set_wifi_connections(provider, enabled).await });
+
+    if let (Ok(()), true, Some(usbmuxd)) = (&res, enabled, provider.usbmuxd()) {
+        let udid = usbmuxd.udid.clone();
+        let wait_timeout = Duration::from_secs(timeout_secs.into());
+
+        GLOBAL_RUNTIME.spawn(async move {
+            if idevice_helper::wait_for_network_device(&udid, wait_timeout).await {
+                push_event(Event::NetworkConnected);
+            }
+        });
+    }
+
+    res.into()
+}
+
+/// png bytes in a `ByteBuffer`, needs the developer disk image to be mounted
+#[unsafe(no_mangle)]
+pub extern "C" fn take_screenshot(provider: *mut DeviceProvider) -> RustResult {
+    let provider = ffi_try!(provider_arg(provider));
+
+    bytes_result(run_sync(idevice_screenshot::take_screenshot(provider)))
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn list_path_ffi(
     path: *const c_char,
-    provider: *mut UsbmuxdProvider,
+    provider: *mut DeviceProvider,
 ) -> *mut FSTreeFFI {
     let c_str = unsafe { std::ffi::CStr::from_ptr(path) };
     let path_str = c_str.to_str().unwrap_or("/");
@@ -151,7 +1073,7 @@ pub extern "C" fn list_path_ffi(
 pub extern "C" fn download_a_file(
     path: *const c_char,
     dest: *const c_char,
-    provider: *mut UsbmuxdProvider,
+    provider: *mut DeviceProvider,
     callback: extern "C" fn(u8),
 ) {
     let path_c_str = unsafe { std::ffi::CStr::from_ptr(path) };
@@ -205,10 +1127,26 @@ pub extern "C" fn download_a_file(
     });
 }
 
+/// json with `usbmuxd_address`, `label`, `connect_timeout_secs` and
+/// `max_concurrent_operations`, all optional. null goes back to the defaults
+#[unsafe(no_mangle)]
+pub extern "C" fn configure(config: *const c_char) -> RustResult {
+    json_result(json_arg(config).and_then(idevice_config::configure))
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn get_provider() -> RustResult {
+    get_provider_by_connection(ConnectionKind::Any as u32)
+}
+
+/// a provider for a device connected through `kind`, a `ConnectionKind`. `Any` prefers usb
+/// over the network
+#[unsafe(no_mangle)]
+pub extern "C" fn get_provider_by_connection(kind: u32) -> RustResult {
+    let kind = ffi_try!(ConnectionKind::try_from(kind));
+
     match panic::catch_unwind(|| {
-        let provider = run_sync(idevice_helper::get_provider());
+        let provider = run_sync(idevice_helper::get_provider(kind));
 
         match provider {
             Ok(p) => RustResult {
@@ -252,6 +1190,18 @@ pub extern "C" fn free_c_string(s: *mut c_char) {
     }
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn free_byte_buffer(buffer: *mut ByteBuffer) {
+    let Some(buffer) = take_box(buffer) else {
+        return;
+    };
+
+    drop(take_box(ptr::slice_from_raw_parts_mut(
+        buffer.data,
+        buffer.len,
+    )));
+}
+
 #[unsafe(no_mangle)]
 pub extern "C" fn free_lockdownd_client(lockdownd: *mut LockdownClient) {
     if !lockdownd.is_null() {
@@ -262,7 +1212,7 @@ pub extern "C" fn free_lockdownd_client(lockdownd: *mut LockdownClient) {
 }
 
 #[unsafe(no_mangle)]
-pub extern "C" fn free_provider(provider: *mut UsbmuxdProvider) {
+pub extern "C" fn free_provider(provider: *mut DeviceProvider) {
     if !provider.is_null() {
         unsafe {
             Box::from_raw(provider);
@@ -1,5 +1,18 @@
+mod idevice_apps;
+mod idevice_config;
+mod idevice_crash;
+mod idevice_diag;
 mod idevice_fs;
 mod idevice_helper;
+mod idevice_ipa;
+mod idevice_models;
+mod idevice_pairing;
+mod idevice_provider;
+mod idevice_region;
+mod idevice_screenshot;
+mod idevice_settings;
+mod idevice_springboard;
+mod idevice_syslog;
 mod idevice_utils;
 
 use std::collections::HashMap;